@@ -1,6 +1,9 @@
 use std::time::Instant;
 
-use light_raytracer::{Camera, Environment, Geometry, Renderer, RendererSettings};
+use light_raytracer::{
+    Camera, Environment, Geometry, GltfCamera, GltfSelection, Integrator, Light, Renderer,
+    RendererSettings, Sky,
+};
 use winit::{
     dpi::PhysicalSize,
     event::{DeviceEvent, Event, KeyEvent, WindowEvent},
@@ -51,13 +54,26 @@ pub async fn run() {
         .unwrap();
 }
 
+const SKY_SIZE: glam::UVec2 = glam::uvec2(512, 256);
+// baking the sky is slow, while the sun is animated only its light follows it every frame
+const SKY_BAKE_INTERVAL: f32 = 0.5;
+
 struct App {
     window: Window,
     wgpu_context: WgpuContext,
     camera: Camera,
     camera_controller: CameraController,
+    scene_cameras: Vec<GltfCamera>,
+    selected_camera: usize,
     renderer_settings: RendererSettings,
     renderer: Renderer,
+    environment: Environment,
+    geometry: Geometry,
+    selected_material: usize,
+    sky: Sky,
+    use_sky: bool,
+    animate_sun: bool,
+    sky_age: f32,
     ui_layer: UiLayer,
     frame_time: f32,
 }
@@ -74,20 +90,23 @@ impl App {
 
         let renderer_settings = RendererSettings::default();
 
-        let camera = Camera {
-            position: glam::vec3(0.0, 0.0, 8.0),
-            forward: glam::vec3(0.0, 0.0, -1.0),
-            aspect: size.x as f32 / size.y as f32,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 1000.0,
-        };
-
         let camera_controller = CameraController::new(4.0, 0.1);
 
         let environment = Environment::load("assets/rural_crossroads_1k.hdr").unwrap();
 
-        let geometry = Geometry::load("assets/basic.gltf", "Scene").unwrap();
+        let (geometry, scene_cameras) =
+            Geometry::load_with_cameras("assets/basic.gltf", GltfSelection::DefaultScene).unwrap();
+
+        // starts from the first camera of the scene if there is one
+        let mut camera = scene_cameras
+            .first()
+            .map(|scene_camera| scene_camera.camera.clone())
+            .unwrap_or(Camera {
+                position: glam::vec3(0.0, 0.0, 8.0),
+                forward: glam::vec3(0.0, 0.0, -1.0),
+                ..Default::default()
+            });
+        camera.aspect = size.x as f32 / size.y as f32;
 
         let renderer = Renderer::new(
             device,
@@ -96,9 +115,10 @@ impl App {
             format,
             renderer_settings.clone(),
             camera.clone(),
-            environment,
-            geometry,
-        );
+            environment.clone(),
+            geometry.clone(),
+        )
+        .unwrap();
 
         let ui_layer = UiLayer::new(&window, device, format, 1);
 
@@ -107,13 +127,48 @@ impl App {
             wgpu_context,
             camera,
             camera_controller,
+            scene_cameras,
+            selected_camera: 0,
             renderer_settings,
             renderer,
+            environment,
+            geometry,
+            selected_material: 0,
+            sky: Sky::default(),
+            use_sky: false,
+            animate_sun: false,
+            sky_age: 0.0,
             ui_layer,
             frame_time: 0.0,
         }
     }
 
+    // the lights of the scene plus the sun of the procedural sky
+    fn lights(&self) -> Vec<Light> {
+        let mut lights = self.geometry.lights.clone();
+        if self.use_sky {
+            lights.push(self.sky.sun_light());
+        }
+        lights
+    }
+
+    // swaps between the loaded environment and the procedural sky with its sun light
+    fn update_sky(&mut self) {
+        self.sky_age = 0.0;
+        let environment = if self.use_sky {
+            Environment::from_sky(&self.sky, SKY_SIZE)
+        } else {
+            self.environment.clone()
+        };
+        if let Err(err) = self
+            .renderer
+            .update_environment(environment)
+            .and_then(|_| self.renderer.update_lights(&self.lights()))
+        {
+            log::error!("failed to update the sky: {}", err);
+        }
+    }
+
     fn on_window_event(&mut self, event: &WindowEvent) {
         self.ui_layer.on_window_event(&self.window, event);
 
@@ -140,7 +195,9 @@ impl App {
                 self.wgpu_context.resize(new_size);
                 self.renderer.resize(new_size);
                 self.camera.aspect = new_size.x as f32 / new_size.y as f32;
-                self.renderer.update_camera(self.camera.clone());
+                if let Err(err) = self.renderer.update_camera(self.camera.clone()) {
+                    log::error!("failed to update the camera: {}", err);
+                }
             }
             _ => {}
         }
@@ -161,7 +218,19 @@ impl App {
             .camera_controller
             .update(dt, &self.window, &mut self.camera)
         {
-            self.renderer.update_camera(self.camera.clone());
+            if let Err(err) = self.renderer.update_camera(self.camera.clone()) {
+                log::error!("failed to update the camera: {}", err);
+            }
+        }
+
+        if self.use_sky && self.animate_sun {
+            self.sky.sun_azimuth = (self.sky.sun_azimuth + 0.2 * dt) % std::f32::consts::TAU;
+            self.sky_age += dt;
+            if self.sky_age >= SKY_BAKE_INTERVAL {
+                self.update_sky();
+            } else if let Err(err) = self.renderer.update_lights(&self.lights()) {
+                log::error!("failed to update the sun: {}", err);
+            }
         }
     }
 
@@ -182,6 +251,8 @@ impl App {
         self.renderer
             .render(device, queue, &mut encoder, &frame_view);
 
+        let mut sky_changed = false;
+
         self.ui_layer.render(
             &self.window,
             device,
@@ -196,6 +267,17 @@ impl App {
                         "Frames per Second: {}",
                         (1.0 / self.frame_time) as u32
                     ));
+                    let memory_usage = self.renderer.memory_usage();
+                    ui.label(format!(
+                        "GPU Memory: {:.1} MB",
+                        memory_usage.total() as f32 / (1024.0 * 1024.0)
+                    ));
+                    if memory_usage.exceeds_limits() {
+                        ui.label(format!(
+                            "A storage buffer exceeds the device limit of {:.1} MB",
+                            memory_usage.max_storage_buffer as f32 / (1024.0 * 1024.0)
+                        ));
+                    }
                     if ui.button("Reset").clicked() {
                         self.renderer.reset();
                     }
@@ -237,6 +319,63 @@ impl App {
                                 .changed();
                             ui.end_row();
 
+                            if !self.scene_cameras.is_empty() {
+                                ui.label("Camera");
+                                let camera_name = |index: usize, camera: &GltfCamera| {
+                                    camera
+                                        .name
+                                        .clone()
+                                        .unwrap_or_else(|| format!("Camera {}", index))
+                                };
+                                let mut camera_changed = false;
+                                egui::ComboBox::from_id_source("camera")
+                                    .selected_text(camera_name(
+                                        self.selected_camera,
+                                        &self.scene_cameras[self.selected_camera],
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for (index, camera) in self.scene_cameras.iter().enumerate()
+                                        {
+                                            camera_changed |= ui
+                                                .selectable_value(
+                                                    &mut self.selected_camera,
+                                                    index,
+                                                    camera_name(index, camera),
+                                                )
+                                                .clicked();
+                                        }
+                                    });
+                                if camera_changed {
+                                    let aspect = self.camera.aspect;
+                                    self.camera =
+                                        self.scene_cameras[self.selected_camera].camera.clone();
+                                    self.camera.aspect = aspect;
+                                    if let Err(err) =
+                                        self.renderer.update_camera(self.camera.clone())
+                                    {
+                                        log::error!("failed to update the camera: {}", err);
+                                    }
+                                }
+                                ui.end_row();
+                            }
+
+                            ui.label("Integrator");
+                            egui::ComboBox::from_id_source("integrator")
+                                .selected_text(format!("{:?}", self.renderer_settings.integrator))
+                                .show_ui(ui, |ui| {
+                                    for integrator in [Integrator::PathTracer, Integrator::Preview]
+                                    {
+                                        changed |= ui
+                                            .selectable_value(
+                                                &mut self.renderer_settings.integrator,
+                                                integrator,
+                                                format!("{:?}", integrator),
+                                            )
+                                            .changed();
+                                    }
+                                });
+                            ui.end_row();
+
                             ui.label("Furnace Test");
                             changed |= ui
                                 .add(egui::Checkbox::new(
@@ -259,16 +398,122 @@ impl App {
                                 .changed();
                             ui.end_row();
 
+                            ui.label("Exposure");
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut self.renderer_settings.exposure)
+                                        .speed(0.01)
+                                        .fixed_decimals(2)
+                                        .clamp_range(0.01..=100.0),
+                                )
+                                .changed();
+                            ui.end_row();
+
                             if changed {
-                                self.renderer
-                                    .update_settings(self.renderer_settings.clone());
+                                if let Err(err) = self
+                                    .renderer
+                                    .update_settings(self.renderer_settings.clone())
+                                {
+                                    log::error!("failed to update the settings: {}", err);
+                                }
                             }
                         });
+
+                    ui.separator();
+
+                    ui.heading("Material");
+
+                    egui::Grid::new("Material")
+                        .num_columns(2)
+                        .spacing([15.0, 4.0])
+                        .show(ui, |ui| {
+                            ui.label("Index");
+                            ui.add(
+                                egui::DragValue::new(&mut self.selected_material)
+                                    .clamp_range(0..=self.geometry.materials.len() - 1),
+                            );
+                            ui.end_row();
+
+                            let material = &mut self.geometry.materials[self.selected_material];
+
+                            ui.label("Albedo");
+                            let mut albedo = material.albedo.to_array();
+                            let mut changed = ui.color_edit_button_rgb(&mut albedo).changed();
+                            material.albedo = albedo.into();
+                            ui.end_row();
+
+                            ui.label("Roughness");
+                            changed |= ui
+                                .add(egui::Slider::new(&mut material.roughness, 0.0..=1.0))
+                                .changed();
+                            ui.end_row();
+
+                            ui.label("Metallic");
+                            changed |= ui
+                                .add(egui::Slider::new(&mut material.metallic, 0.0..=1.0))
+                                .changed();
+                            ui.end_row();
+
+                            if changed {
+                                if let Err(err) = self
+                                    .renderer
+                                    .update_material(self.selected_material, material.clone())
+                                {
+                                    log::error!("failed to update the material: {}", err);
+                                }
+                            }
+                        });
+
+                    ui.separator();
+
+                    ui.heading("Sky");
+
+                    egui::Grid::new("Sky")
+                        .num_columns(2)
+                        .spacing([15.0, 4.0])
+                        .show(ui, |ui| {
+                            ui.label("Procedural Sky");
+                            sky_changed |=
+                                ui.add(egui::Checkbox::new(&mut self.use_sky, "")).changed();
+                            ui.end_row();
+
+                            ui.label("Sun Elevation");
+                            sky_changed |= ui
+                                .add(egui::Slider::new(
+                                    &mut self.sky.sun_elevation,
+                                    -0.2..=std::f32::consts::FRAC_PI_2,
+                                ))
+                                .changed();
+                            ui.end_row();
+
+                            ui.label("Sun Azimuth");
+                            sky_changed |= ui
+                                .add(egui::Slider::new(
+                                    &mut self.sky.sun_azimuth,
+                                    0.0..=std::f32::consts::TAU,
+                                ))
+                                .changed();
+                            ui.end_row();
+
+                            ui.label("Turbidity");
+                            sky_changed |= ui
+                                .add(egui::Slider::new(&mut self.sky.turbidity, 1.7..=10.0))
+                                .changed();
+                            ui.end_row();
+
+                            ui.label("Animate Sun");
+                            ui.add(egui::Checkbox::new(&mut self.animate_sun, ""));
+                            ui.end_row();
+                        });
                 });
             },
         );
 
         queue.submit(std::iter::once(encoder.finish()));
         frame.present();
+
+        if sky_changed {
+            self.update_sky();
+        }
     }
 }
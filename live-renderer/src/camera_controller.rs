@@ -111,6 +111,7 @@ impl CameraController {
             let rotation = glam::Quat::from_axis_angle(right, -pitch_delta)
                 .mul_quat(glam::Quat::from_axis_angle(up, -yaw_delta));
             camera.forward = rotation.mul_vec3(camera.forward);
+            camera.up = rotation.mul_vec3(camera.up);
 
             self.mouse_delta = glam::Vec2::ZERO;
             updated = true;
@@ -1,11 +1,28 @@
+use crate::ValidationError;
+
 #[derive(Clone, Debug)]
 pub struct Camera {
     pub position: glam::Vec3,
     pub forward: glam::Vec3,
+    // rolls the camera around `forward`, it doesn't need to be orthogonal to it
+    pub up: glam::Vec3,
     pub aspect: f32,
     pub fovy: f32,
     pub znear: f32,
+    // may be infinite for perspective projections
     pub zfar: f32,
+    pub projection: Projection,
+}
+
+// `fovy` only applies to perspective projections, orthographic ones cover `half_height` above and
+// below the view axis
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    Orthographic {
+        half_height: f32,
+    },
 }
 
 impl Default for Camera {
@@ -13,22 +30,78 @@ impl Default for Camera {
         Self {
             position: glam::vec3(0.0, 0.0, 1.0),
             forward: -glam::Vec3::Z,
+            up: glam::Vec3::Y,
             aspect: 1.0,
             fovy: 45.0,
             znear: 0.1,
             zfar: 1000.0,
+            projection: Projection::Perspective,
         }
     }
 }
 
 impl Camera {
-    pub fn validate(&self) -> bool {
-        self.aspect > 0.0 && self.znear > 0.0 && self.zfar > 0.0
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !self.position.is_finite() || !self.forward.is_finite() || !self.up.is_finite() {
+            return Err(ValidationError::Camera(
+                "position, forward and up must be finite",
+            ));
+        }
+        if self.forward.length_squared() == 0.0 || self.up.length_squared() == 0.0 {
+            return Err(ValidationError::Camera("forward and up must not be zero"));
+        }
+        if !(self.aspect > 0.0 && self.aspect.is_finite()) {
+            return Err(ValidationError::Camera("aspect must be positive"));
+        }
+        match self.projection {
+            Projection::Perspective if !(self.fovy > 0.0 && self.fovy < 180.0) => {
+                return Err(ValidationError::Camera(
+                    "fovy must be between 0 and 180 degrees",
+                ));
+            }
+            Projection::Orthographic { half_height }
+                if !(half_height > 0.0 && half_height.is_finite()) =>
+            {
+                return Err(ValidationError::Camera("half height must be positive"));
+            }
+            Projection::Orthographic { .. } if !self.zfar.is_finite() => {
+                return Err(ValidationError::Camera(
+                    "zfar must be finite for orthographic projections",
+                ));
+            }
+            _ => {}
+        }
+        // zfar may be infinite for perspective projections, but not nan
+        if !(self.znear > 0.0 && self.znear.is_finite() && self.zfar > self.znear) {
+            return Err(ValidationError::Camera(
+                "znear must be positive and smaller than zfar",
+            ));
+        }
+        Ok(())
     }
 
     pub fn compute_projection(&self) -> glam::Mat4 {
-        let fovy_radians = std::f32::consts::PI / 180.0 * self.fovy;
-        glam::Mat4::perspective_rh(fovy_radians, self.aspect, self.znear, self.zfar)
+        match self.projection {
+            Projection::Perspective => {
+                let fovy_radians = std::f32::consts::PI / 180.0 * self.fovy;
+                if self.zfar.is_finite() {
+                    glam::Mat4::perspective_rh(fovy_radians, self.aspect, self.znear, self.zfar)
+                } else {
+                    glam::Mat4::perspective_infinite_rh(fovy_radians, self.aspect, self.znear)
+                }
+            }
+            Projection::Orthographic { half_height } => {
+                let half_width = half_height * self.aspect;
+                glam::Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        }
     }
 
     pub fn compute_inverse_projection(&self) -> glam::Mat4 {
@@ -36,11 +109,17 @@ impl Camera {
     }
 
     pub fn compute_view(&self) -> glam::Mat4 {
-        glam::Mat4::look_at_rh(
-            self.position,
-            self.position + self.forward,
-            glam::vec3(0.0, 1.0, 0.0),
-        )
+        // looking along the up vector, like a top view with the default up, keeps -z or +z at the
+        // top of the image
+        let forward = self.forward.normalize();
+        let up = self.up.normalize();
+        let cos_up = forward.dot(up);
+        let up = if cos_up.abs() > 0.9999 {
+            up.any_orthonormal_vector() * -cos_up.signum()
+        } else {
+            up
+        };
+        glam::Mat4::look_at_rh(self.position, self.position + forward, up)
     }
 
     pub fn compute_inverse_view(&self) -> glam::Mat4 {
@@ -52,7 +131,7 @@ impl Camera {
 #[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuCamera {
     position: glam::Vec3,
-    pad0: u32,
+    orthographic: u32,
     inverse_projection: glam::Mat4,
     inverse_view: glam::Mat4,
 }
@@ -61,7 +140,7 @@ impl From<Camera> for GpuCamera {
     fn from(camera: Camera) -> Self {
         Self {
             position: camera.position,
-            pad0: 0,
+            orthographic: matches!(camera.projection, Projection::Orthographic { .. }).into(),
             inverse_projection: camera.compute_inverse_projection(),
             inverse_view: camera.compute_inverse_view(),
         }
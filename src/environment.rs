@@ -1,3 +1,6 @@
+use crate::ValidationError;
+
+#[derive(Clone)]
 pub struct Environment {
     pub size: glam::UVec2,
     pub data: Vec<u8>,
@@ -13,32 +16,400 @@ impl Default for Environment {
 }
 
 impl Environment {
+    // loads an equirectangular panorama, .hdr and .exr are read as linear radiance while .png and
+    // .jpg/.jpeg are converted from srgb
+    #[cfg(feature = "image")]
+    pub fn load(path: &str) -> Result<Self, EnvironmentError> {
+        let (size, pixels) = read_linear_image(path)?;
+        Ok(Self::from_pixels(size, &pixels))
+    }
+
+    // loads a cube map laid out as a horizontal cross:
+    //        +y
+    //    -x  +z  +x  -z
+    //        -y
+    #[cfg(feature = "image")]
+    pub fn load_cube_cross(path: &str) -> Result<Self, EnvironmentError> {
+        let (size, pixels) = read_linear_image(path)?;
+        let face_size = size.x / 4;
+        if face_size == 0 || size.x != 4 * face_size || size.y != 3 * face_size {
+            return Err(EnvironmentError::InvalidCubeMap(format!(
+                "expected a 4:3 horizontal cross, got {}x{}",
+                size.x, size.y
+            )));
+        }
+
+        // position of the faces in the cross in +x, -x, +y, -y, +z, -z order
+        let offsets = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+        let faces = offsets.map(|(column, row)| {
+            let mut face = Vec::with_capacity((face_size * face_size) as usize);
+            for y in 0..face_size {
+                let start = ((row * face_size + y) * size.x + column * face_size) as usize;
+                face.extend_from_slice(&pixels[start..start + face_size as usize]);
+            }
+            face
+        });
+
+        Ok(Self::from_cube_faces(face_size, &faces))
+    }
+
+    // loads a cube map from separate images in +x, -x, +y, -y, +z, -z order
     #[cfg(feature = "image")]
-    pub fn load(path: &str) -> Result<Self, image::ImageError> {
-        use image::codecs::hdr::HdrDecoder;
-        use std::{fs, io::Cursor};
-
-        let img_data = fs::read(path)?;
-
-        let hdr_decoder = HdrDecoder::new(Cursor::new(img_data))?;
-        let meta = hdr_decoder.metadata();
-        let mut pixels = vec![[0.0, 0.0, 0.0, 0.0]; meta.width as usize * meta.height as usize];
-        hdr_decoder.read_image_transform(
-            |pix| {
-                let rgb = pix.to_hdr();
-                [rgb.0[0], rgb.0[1], rgb.0[2], 1.0f32]
-            },
-            &mut pixels[..],
-        )?;
-
-        Ok(Self {
-            size: glam::uvec2(meta.width, meta.height),
-            data: bytemuck::cast_slice(&pixels).to_vec(),
-        })
-    }
-
-    pub fn validate(&self) -> bool {
-        (self.size.x * self.size.y * std::mem::size_of::<glam::Vec4>() as u32) as usize
-            == self.data.len()
+    pub fn load_cube_faces(paths: [&str; 6]) -> Result<Self, EnvironmentError> {
+        let mut face_size = 0;
+        let mut faces: [Vec<glam::Vec4>; 6] = Default::default();
+        for (i, path) in paths.iter().enumerate() {
+            let (size, pixels) = read_linear_image(path)?;
+            if size.x != size.y || (i > 0 && size.x != face_size) {
+                return Err(EnvironmentError::InvalidCubeMap(format!(
+                    "face {} is {}x{}, faces must be square and of the same size",
+                    path, size.x, size.y
+                )));
+            }
+            face_size = size.x;
+            faces[i] = pixels;
+        }
+
+        Ok(Self::from_cube_faces(face_size, &faces))
+    }
+
+    // resamples linear cube map faces (+x, -x, +y, -y, +z, -z) to an equirectangular environment
+    pub fn from_cube_faces(face_size: u32, faces: &[Vec<glam::Vec4>; 6]) -> Self {
+        let size = glam::uvec2(4 * face_size, 2 * face_size);
+
+        let mut pixels = Vec::with_capacity((size.x * size.y) as usize);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let (face, uv) = cube_face_uv(equirect_direction(x, y, size));
+                pixels.push(sample_bilinear(
+                    &faces[face],
+                    glam::UVec2::splat(face_size),
+                    uv,
+                ));
+            }
+        }
+
+        Self::from_pixels(size, &pixels)
+    }
+
+    pub fn from_pixels(size: glam::UVec2, pixels: &[glam::Vec4]) -> Self {
+        Self {
+            size,
+            data: bytemuck::cast_slice(pixels).to_vec(),
+        }
+    }
+
+    // halves the resolution with a box filter until both dimensions fit in `max_dimension`
+    pub fn downscaled(&self, max_dimension: u32) -> Self {
+        let mut size = self.size;
+        let mut pixels = self.pixels();
+        while size.x > max_dimension || size.y > max_dimension {
+            (size, pixels) = half_resolution(size, &pixels);
+        }
+
+        Self::from_pixels(size, &pixels)
+    }
+
+    // box filtered mip levels down to 1x1, starting with the environment itself
+    pub(crate) fn mip_chain(&self) -> Vec<(glam::UVec2, Vec<glam::Vec4>)> {
+        let mut levels = vec![(self.size, self.pixels())];
+        while let Some((size, pixels)) = levels.last().filter(|(size, _)| size.max_element() > 1) {
+            let level = half_resolution(*size, pixels);
+            levels.push(level);
+        }
+        levels
+    }
+
+    // the data may not be aligned for vec4
+    pub fn pixels(&self) -> Vec<glam::Vec4> {
+        self.data
+            .chunks_exact(std::mem::size_of::<glam::Vec4>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.size.x == 0 || self.size.y == 0 {
+            return Err(ValidationError::EmptyEnvironment);
+        }
+        if self.size.x as usize * self.size.y as usize * std::mem::size_of::<glam::Vec4>()
+            != self.data.len()
+        {
+            return Err(ValidationError::EnvironmentDataSize {
+                size: self.size,
+                len: self.data.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn half_resolution(size: glam::UVec2, pixels: &[glam::Vec4]) -> (glam::UVec2, Vec<glam::Vec4>) {
+    let new_size = (size / 2).max(glam::UVec2::ONE);
+    let texel = |x: u32, y: u32| pixels[(y.min(size.y - 1) * size.x + x.min(size.x - 1)) as usize];
+
+    let mut new_pixels = Vec::with_capacity((new_size.x * new_size.y) as usize);
+    for y in 0..new_size.y {
+        for x in 0..new_size.x {
+            new_pixels.push(
+                0.25 * (texel(2 * x, 2 * y)
+                    + texel(2 * x + 1, 2 * y)
+                    + texel(2 * x, 2 * y + 1)
+                    + texel(2 * x + 1, 2 * y + 1)),
+            );
+        }
+    }
+
+    (new_size, new_pixels)
+}
+
+// direction through the center of a texel of an equirectangular environment, matching
+// sample_environment in the shader
+pub(crate) fn equirect_direction(x: u32, y: u32, size: glam::UVec2) -> glam::Vec3 {
+    let phi = ((x as f32 + 0.5) / size.x as f32 - 0.5) * 2.0 * std::f32::consts::PI;
+    let elevation = (0.5 - (y as f32 + 0.5) / size.y as f32) * std::f32::consts::PI;
+    glam::vec3(
+        elevation.cos() * phi.cos(),
+        elevation.sin(),
+        elevation.cos() * phi.sin(),
+    )
+}
+
+// projects the radiance onto l2 spherical harmonics and convolves it with the clamped cosine
+// (Ramamoorthi and Hanrahan), so that `eval_sh` gives the irradiance over pi along a normal
+pub(crate) fn irradiance_sh(size: glam::UVec2, pixels: &[glam::Vec4]) -> [glam::Vec3; 9] {
+    // cosine convolution per band divided by pi
+    const BAND_SCALES: [f32; 9] = [
+        1.0,
+        2.0 / 3.0,
+        2.0 / 3.0,
+        2.0 / 3.0,
+        0.25,
+        0.25,
+        0.25,
+        0.25,
+        0.25,
+    ];
+
+    let texel_area = 2.0 * std::f32::consts::PI * std::f32::consts::PI / (size.x * size.y) as f32;
+    let mut coefficients = [glam::Vec3::ZERO; 9];
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let direction = equirect_direction(x, y, size);
+            // texels shrink towards the poles
+            let solid_angle = texel_area * (1.0 - direction.y * direction.y).sqrt();
+            let radiance = pixels[(y * size.x + x) as usize].truncate() * solid_angle;
+            for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(direction)) {
+                *coefficient += radiance * basis;
+            }
+        }
+    }
+
+    for (coefficient, scale) in coefficients.iter_mut().zip(BAND_SCALES) {
+        *coefficient *= scale;
+    }
+    coefficients
+}
+
+// real spherical harmonics up to the second band, matching eval_sh in the shader
+fn sh_basis(direction: glam::Vec3) -> [f32; 9] {
+    let glam::Vec3 { x, y, z } = direction;
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+// face index and texture coordinates of a direction, using the usual cube map conventions with
+// the first row of each face at the top
+fn cube_face_uv(direction: glam::Vec3) -> (usize, glam::Vec2) {
+    let abs = direction.abs();
+    let (face, major, sc, tc) = if abs.x >= abs.y && abs.x >= abs.z {
+        if direction.x > 0.0 {
+            (0, abs.x, -direction.z, -direction.y)
+        } else {
+            (1, abs.x, direction.z, -direction.y)
+        }
+    } else if abs.y >= abs.z {
+        if direction.y > 0.0 {
+            (2, abs.y, direction.x, direction.z)
+        } else {
+            (3, abs.y, direction.x, -direction.z)
+        }
+    } else if direction.z > 0.0 {
+        (4, abs.z, direction.x, -direction.y)
+    } else {
+        (5, abs.z, -direction.x, -direction.y)
+    };
+
+    (face, 0.5 * (glam::vec2(sc, tc) / major + 1.0))
+}
+
+fn sample_bilinear(pixels: &[glam::Vec4], size: glam::UVec2, uv: glam::Vec2) -> glam::Vec4 {
+    let coord = (uv * size.as_vec2() - 0.5).clamp(glam::Vec2::ZERO, (size - 1).as_vec2());
+    let base = coord.floor().as_uvec2();
+    let next = (base + 1).min(size - 1);
+    let t = coord - coord.floor();
+
+    let texel = |x: u32, y: u32| pixels[(y * size.x + x) as usize];
+    let top = texel(base.x, base.y).lerp(texel(next.x, base.y), t.x);
+    let bottom = texel(base.x, next.y).lerp(texel(next.x, next.y), t.x);
+    top.lerp(bottom, t.y)
+}
+
+#[cfg(feature = "image")]
+fn read_linear_image(path: &str) -> Result<(glam::UVec2, Vec<glam::Vec4>), EnvironmentError> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "hdr" => {
+            use image::codecs::hdr::HdrDecoder;
+            use std::{fs::File, io::BufReader};
+
+            // image::open converts radiance files to 8 bits
+            let hdr_decoder = HdrDecoder::new(BufReader::new(
+                File::open(path).map_err(image::ImageError::IoError)?,
+            ))?;
+            let meta = hdr_decoder.metadata();
+            let mut pixels = vec![glam::Vec4::ZERO; meta.width as usize * meta.height as usize];
+            hdr_decoder.read_image_transform(
+                |pix| {
+                    let rgb = pix.to_hdr();
+                    glam::vec4(rgb.0[0], rgb.0[1], rgb.0[2], 1.0)
+                },
+                &mut pixels[..],
+            )?;
+            Ok((glam::uvec2(meta.width, meta.height), pixels))
+        }
+        "exr" => {
+            let image = image::open(path)?.into_rgba32f();
+            let size = glam::uvec2(image.width(), image.height());
+            let pixels = image
+                .pixels()
+                .map(|pixel| glam::Vec4::from(pixel.0).truncate().extend(1.0))
+                .collect();
+            Ok((size, pixels))
+        }
+        "png" | "jpg" | "jpeg" => {
+            let image = image::open(path)?.into_rgba8();
+            let size = glam::uvec2(image.width(), image.height());
+            let pixels = image
+                .pixels()
+                .map(|pixel| {
+                    glam::vec4(
+                        srgb_to_linear(pixel.0[0]),
+                        srgb_to_linear(pixel.0[1]),
+                        srgb_to_linear(pixel.0[2]),
+                        1.0,
+                    )
+                })
+                .collect();
+            Ok((size, pixels))
+        }
+        _ => Err(EnvironmentError::UnsupportedFormat(extension)),
+    }
+}
+
+#[cfg(feature = "image")]
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(feature = "image")]
+#[derive(Debug)]
+pub enum EnvironmentError {
+    Image(image::ImageError),
+    UnsupportedFormat(String),
+    InvalidCubeMap(String),
+}
+
+#[cfg(feature = "image")]
+impl std::fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvironmentError::Image(err) => write!(f, "failed to read environment: {}", err),
+            EnvironmentError::UnsupportedFormat(extension) => write!(
+                f,
+                "unsupported environment format {:?}, expected hdr, exr, png or jpg",
+                extension
+            ),
+            EnvironmentError::InvalidCubeMap(reason) => write!(f, "invalid cube map: {}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl std::error::Error for EnvironmentError {}
+
+#[cfg(feature = "image")]
+impl From<image::ImageError> for EnvironmentError {
+    fn from(err: image::ImageError) -> Self {
+        EnvironmentError::Image(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn irradiance(sh: &[glam::Vec3; 9], normal: glam::Vec3) -> glam::Vec3 {
+        sh.iter()
+            .zip(sh_basis(normal))
+            .map(|(coefficient, basis)| *coefficient * basis)
+            .sum()
+    }
+
+    #[test]
+    fn constant_irradiance() {
+        let size = glam::uvec2(64, 32);
+        let pixels = vec![glam::Vec4::ONE; (size.x * size.y) as usize];
+        let sh = irradiance_sh(size, &pixels);
+        for normal in [
+            glam::Vec3::X,
+            glam::Vec3::Y,
+            glam::Vec3::NEG_Y,
+            glam::Vec3::Z,
+        ] {
+            assert!(
+                (irradiance(&sh, normal) - glam::Vec3::ONE)
+                    .abs()
+                    .max_element()
+                    < 0.01
+            );
+        }
+    }
+
+    #[test]
+    fn sky_above_ground() {
+        let size = glam::uvec2(64, 32);
+        let pixels: Vec<glam::Vec4> = (0..size.x * size.y)
+            .map(|i| {
+                if i / size.x < size.y / 2 {
+                    glam::Vec4::ONE
+                } else {
+                    glam::Vec4::ZERO
+                }
+            })
+            .collect();
+        let sh = irradiance_sh(size, &pixels);
+        assert!(irradiance(&sh, glam::Vec3::Y).x > 0.9);
+        assert!(irradiance(&sh, glam::Vec3::NEG_Y).x < 0.1);
+        assert!((irradiance(&sh, glam::Vec3::X).x - 0.5).abs() < 0.01);
     }
 }
@@ -0,0 +1,220 @@
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Aabb {
+    // inverted so that rays never hit it and any union replaces it
+    pub const EMPTY: Aabb = Aabb {
+        min: glam::Vec3::splat(f32::MAX),
+        max: glam::Vec3::splat(f32::MIN),
+    };
+
+    pub fn from_points(points: &[glam::Vec3]) -> Aabb {
+        points.iter().fold(Aabb::EMPTY, |aabb, point| Aabb {
+            min: aabb.min.min(*point),
+            max: aabb.max.max(*point),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn transformed(&self, transform: glam::Mat4) -> Aabb {
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            transform.transform_point3(glam::vec3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            ))
+        });
+        Aabb::from_points(&corners)
+    }
+
+    fn centroid(&self) -> glam::Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.x * d.z + d.y * d.z)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuBvhNode {
+    min: glam::Vec3,
+    // first item for leaves, index of the first child otherwise (the second one follows it)
+    first: u32,
+    max: glam::Vec3,
+    // zero for interior nodes
+    count: u32,
+}
+
+impl GpuBvhNode {
+    fn new(aabb: &Aabb, first: u32, count: u32) -> Self {
+        Self {
+            min: aabb.min,
+            first,
+            max: aabb.max,
+            count,
+        }
+    }
+}
+
+// deepest level of a bvh, the traversal stack of the shader (BVH_STACK_SIZE) holds a pending
+// sibling for every level above the current node plus its two children
+const MAX_DEPTH: usize = 31;
+
+// builds a bvh over the items with the binned surface area heuristic and appends it to `nodes`.
+// the items are reordered so that leaves reference consecutive items, `item_offset` is added to
+// the item indices of leaves. returns the index of the root, an empty tree is a single node with
+// empty bounds
+pub(super) fn build_bvh(
+    items: &mut [(u32, Aabb)],
+    item_offset: u32,
+    max_leaf_size: usize,
+    nodes: &mut Vec<GpuBvhNode>,
+) -> u32 {
+    let root = nodes.len();
+    nodes.push(GpuBvhNode::new(&Aabb::EMPTY, 0, 0));
+    if !items.is_empty() {
+        subdivide(root, items, item_offset, max_leaf_size, 0, nodes);
+        debug_assert!(depth(nodes, root) <= MAX_DEPTH);
+    }
+    root as u32
+}
+
+fn subdivide(
+    node_index: usize,
+    items: &mut [(u32, Aabb)],
+    first_item: u32,
+    max_leaf_size: usize,
+    depth: usize,
+    nodes: &mut Vec<GpuBvhNode>,
+) {
+    let aabb = items
+        .iter()
+        .fold(Aabb::EMPTY, |aabb, (_, item)| aabb.union(item));
+
+    // nodes at the deepest level become leaves however many items they hold
+    let mid = if items.len() > max_leaf_size && depth < MAX_DEPTH {
+        find_split(items, aabb.surface_area())
+    } else {
+        None
+    };
+    let Some(mid) = mid else {
+        nodes[node_index] = GpuBvhNode::new(&aabb, first_item, items.len() as u32);
+        return;
+    };
+
+    let left = nodes.len();
+    nodes.push(GpuBvhNode::default());
+    nodes.push(GpuBvhNode::default());
+    nodes[node_index] = GpuBvhNode::new(&aabb, left as u32, 0);
+
+    let (left_items, right_items) = items.split_at_mut(mid);
+    subdivide(
+        left,
+        left_items,
+        first_item,
+        max_leaf_size,
+        depth + 1,
+        nodes,
+    );
+    subdivide(
+        left + 1,
+        right_items,
+        first_item + mid as u32,
+        max_leaf_size,
+        depth + 1,
+        nodes,
+    );
+}
+
+fn depth(nodes: &[GpuBvhNode], node_index: usize) -> usize {
+    let node = &nodes[node_index];
+    if node.count > 0 {
+        return 0;
+    }
+    let left = node.first as usize;
+    1 + depth(nodes, left).max(depth(nodes, left + 1))
+}
+
+// sorts the items along the best split axis and returns the split position, or none if keeping
+// them in a leaf is cheaper
+fn find_split(items: &mut [(u32, Aabb)], parent_area: f32) -> Option<usize> {
+    const NUM_BUCKETS: usize = 12;
+
+    let (centroid_min, centroid_max) = items.iter().fold(
+        (glam::Vec3::INFINITY, glam::Vec3::NEG_INFINITY),
+        |(min, max), (_, aabb)| (min.min(aabb.centroid()), max.max(aabb.centroid())),
+    );
+
+    let bucket = |aabb: &Aabb, dim: usize| {
+        let extent = centroid_max[dim] - centroid_min[dim];
+        let offset = (aabb.centroid()[dim] - centroid_min[dim]) / extent;
+        ((offset * NUM_BUCKETS as f32) as usize).min(NUM_BUCKETS - 1)
+    };
+
+    // cost relative to intersecting every item of the parent
+    let mut best: Option<(f32, usize, usize)> = None;
+    for dim in 0..3 {
+        if centroid_max[dim] <= centroid_min[dim] {
+            continue;
+        }
+
+        let mut buckets = [(0usize, Aabb::EMPTY); NUM_BUCKETS];
+        for (_, aabb) in items.iter() {
+            let b = bucket(aabb, dim);
+            buckets[b].0 += 1;
+            buckets[b].1 = buckets[b].1.union(aabb);
+        }
+
+        for split in 0..NUM_BUCKETS - 1 {
+            let side = |buckets: &[(usize, Aabb)]| {
+                buckets
+                    .iter()
+                    .fold((0, Aabb::EMPTY), |(count, aabb), (n, bucket)| {
+                        (count + n, aabb.union(bucket))
+                    })
+            };
+            let (left_count, left) = side(&buckets[..=split]);
+            let (right_count, right) = side(&buckets[split + 1..]);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+
+            let cost = 0.125
+                + (left_count as f32 * left.surface_area()
+                    + right_count as f32 * right.surface_area())
+                    / parent_area.max(f32::MIN_POSITIVE);
+            if !best.is_some_and(|(best_cost, _, _)| cost >= best_cost) {
+                best = Some((cost, dim, split));
+            }
+        }
+    }
+
+    let (cost, dim, split) = best?;
+    // large leaves are always split since their traversal can't be skipped
+    if cost >= items.len() as f32 && items.len() <= 16 {
+        return None;
+    }
+
+    items.sort_by(|a, b| a.1.centroid()[dim].total_cmp(&b.1.centroid()[dim]));
+    let mid = items.partition_point(|(_, aabb)| bucket(aabb, dim) <= split);
+    Some(mid.clamp(1, items.len() - 1))
+}
@@ -1,53 +1,131 @@
-use super::{Material, Triangle, Vertex};
+use crate::ies::IesProfile;
+
+use super::bvh::{self, Aabb, GpuBvhNode};
+use super::light_bvh::{self, GpuLightBvhNode, LightBounds};
+use super::{AlphaMode, Geometry, Light, LightKind, Material, Texture, Triangle, Vertex};
 
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuMaterial {
     albedo: glam::Vec3,
     roughness: f32,
+    emission: glam::Vec3,
     metallic: f32,
+    absorption: glam::Vec3,
+    ior: f32,
+    transmission: f32,
+    thin_walled: u32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    sheen_color: glam::Vec3,
+    sheen_roughness: f32,
+    specular_color: glam::Vec3,
+    specular: f32,
+    alpha: f32,
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    base_color_texture: u32,
+    base_color_tex_coord: u32,
+    normal_texture: u32,
+    normal_tex_coord: u32,
+    normal_scale: f32,
+    double_sided: u32,
     pad0: [u32; 3],
-    emission: glam::Vec3,
-    pad1: u32,
 }
 
-impl From<Material> for GpuMaterial {
-    fn from(material: Material) -> Self {
+impl GpuMaterial {
+    pub fn new(material: &Material, area: f32) -> Self {
+        // Beer-Lambert absorption coefficient of the medium enclosed by the surface
+        let absorption = if material.attenuation_distance.is_finite() {
+            let color = material.attenuation_color.max(glam::Vec3::splat(1e-4));
+            -glam::vec3(color.x.ln(), color.y.ln(), color.z.ln()) / material.attenuation_distance
+        } else {
+            glam::Vec3::ZERO
+        };
+
         Self {
             albedo: material.albedo,
             roughness: material.roughness,
+            emission: material.emitted_radiance(area),
             metallic: material.metallic,
+            absorption,
+            ior: material.ior,
+            transmission: material.transmission,
+            thin_walled: material.thin_walled.into(),
+            clearcoat: material.clearcoat,
+            clearcoat_roughness: material.clearcoat_roughness,
+            sheen_color: material.sheen_color,
+            sheen_roughness: material.sheen_roughness,
+            specular_color: material.specular_color,
+            specular: material.specular,
+            alpha: material.alpha,
+            alpha_mode: match material.alpha_mode {
+                AlphaMode::Opaque => 0,
+                AlphaMode::Mask => 1,
+                AlphaMode::Blend => 2,
+            },
+            alpha_cutoff: material.alpha_cutoff,
+            base_color_texture: material.base_color_texture.unwrap_or(u32::MAX),
+            base_color_tex_coord: material.base_color_tex_coord,
+            normal_texture: material.normal_texture.unwrap_or(u32::MAX),
+            normal_tex_coord: material.normal_tex_coord,
+            normal_scale: material.normal_scale,
+            double_sided: material.double_sided.into(),
             pad0: [0; 3],
-            emission: material.emission,
-            pad1: 0,
         }
     }
 }
 
+// `areas` holds the world space area of every material, see `Geometry::material_areas`
+pub fn convert_materials(materials: &[Material], areas: &[f32]) -> Vec<GpuMaterial> {
+    materials
+        .iter()
+        .zip(areas)
+        .map(|(material, area)| GpuMaterial::new(material, *area))
+        .collect()
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuVertex {
     position: glam::Vec3,
-    pad0: u32,
+    // the color is stored as half floats in the padding of the other attributes
+    color_rg: u32,
     tex_coord: glam::Vec2,
-    pad1: [u32; 2],
+    tex_coord_1: glam::Vec2,
     normal: glam::Vec3,
-    pad2: u32,
+    color_ba: u32,
+    tangent: glam::Vec4,
 }
 
 impl From<Vertex> for GpuVertex {
     fn from(vertex: Vertex) -> Self {
+        let pack = |low: f32, high: f32| {
+            u32::from(half::f16::from_f32(low).to_bits())
+                | u32::from(half::f16::from_f32(high).to_bits()) << 16
+        };
+
         Self {
             position: vertex.position,
-            pad0: 0,
+            color_rg: pack(vertex.color.x, vertex.color.y),
             tex_coord: vertex.tex_coord,
-            pad1: [0; 2],
+            tex_coord_1: vertex.tex_coord_1,
             normal: vertex.normal,
-            pad2: 0,
+            color_ba: pack(vertex.color.z, vertex.color.w),
+            tangent: vertex.tangent,
         }
     }
 }
 
+// geometry made only of primitives has no vertices, but empty storage buffers can't be bound
+pub fn convert_vertices(vertices: &[Vertex]) -> Vec<GpuVertex> {
+    let mut gpu_vertices: Vec<GpuVertex> = vertices.iter().cloned().map(GpuVertex::from).collect();
+    if gpu_vertices.is_empty() {
+        gpu_vertices.push(GpuVertex::default());
+    }
+    gpu_vertices
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuTriangle {
@@ -63,3 +141,565 @@ impl From<Triangle> for GpuTriangle {
         }
     }
 }
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuInstance {
+    transform: glam::Mat4,
+    inverse_transform: glam::Mat4,
+    blas_root: u32,
+    // u32::MAX keeps the materials of the triangles
+    material_index: u32,
+    // 0 for meshes, otherwise the kind of primitive intersected in object space
+    kind: u32,
+    pad0: u32,
+}
+
+// object space bounds and bottom level root of every mesh, enough to rebuild the top level bvh
+// when only instances move
+#[derive(Clone, Debug, Default)]
+pub struct BottomLevels {
+    aabbs: Vec<Aabb>,
+    roots: Vec<u32>,
+}
+
+// builds a bottom level bvh per mesh over its triangles in object space. the nodes start with
+// room for the top level bvh so that it can be rebuilt in place, triangles are reordered to match
+// the leaves and padded since empty storage buffers can't be bound
+pub fn build_bottom_levels(
+    geometry: &Geometry,
+) -> (Vec<GpuTriangle>, Vec<GpuBvhNode>, BottomLevels) {
+    // a binary tree with at least one item per leaf never has more nodes than this
+    let top_level_items = geometry.instances.len() + geometry.primitives.len();
+    let top_level_capacity = (2 * top_level_items).max(2) - 1;
+    let mut nodes = vec![GpuBvhNode::default(); top_level_capacity];
+
+    let mut gpu_triangles = Vec::with_capacity(geometry.triangles.len().max(1));
+    let mut bottom_levels = BottomLevels::default();
+    for mesh in geometry.meshes.iter() {
+        let first = mesh.first_triangle as usize;
+        let triangles = &geometry.triangles[first..first + mesh.num_triangles as usize];
+        let mut items: Vec<(u32, Aabb)> = triangles
+            .iter()
+            .enumerate()
+            .map(|(i, triangle)| {
+                let positions = triangle
+                    .vertex_indices
+                    .map(|i| geometry.vertices[i as usize].position);
+                (i as u32, Aabb::from_points(&positions))
+            })
+            .collect();
+
+        bottom_levels.aabbs.push(
+            items
+                .iter()
+                .fold(Aabb::EMPTY, |aabb, (_, item)| aabb.union(item)),
+        );
+        bottom_levels.roots.push(bvh::build_bvh(
+            &mut items,
+            gpu_triangles.len() as u32,
+            4,
+            &mut nodes,
+        ));
+        gpu_triangles.extend(
+            items
+                .iter()
+                .map(|(i, _)| GpuTriangle::from(triangles[*i as usize].clone())),
+        );
+    }
+
+    if gpu_triangles.is_empty() {
+        gpu_triangles.push(GpuTriangle::default());
+    }
+
+    (gpu_triangles, nodes, bottom_levels)
+}
+
+// builds the top level bvh over the instances and the primitives, its root is node 0 and it fits
+// in the room left by `build_bottom_levels`. primitives become instances without a mesh, all of
+// them are reordered to match the leaves and padded. instances of empty meshes are left out so
+// the length only changes with the meshes and the primitives
+pub fn build_top_level(
+    geometry: &Geometry,
+    bottom_levels: &BottomLevels,
+) -> (Vec<GpuInstance>, Vec<GpuBvhNode>) {
+    let num_instances = geometry.instances.len() as u32;
+    let mut items: Vec<(u32, Aabb)> = geometry
+        .instances
+        .iter()
+        .enumerate()
+        .filter(|(_, instance)| !bottom_levels.aabbs[instance.mesh as usize].is_empty())
+        .map(|(i, instance)| {
+            let aabb = bottom_levels.aabbs[instance.mesh as usize].transformed(instance.transform);
+            (i as u32, aabb)
+        })
+        .chain(
+            geometry
+                .primitives
+                .iter()
+                .enumerate()
+                .map(|(i, primitive)| {
+                    let aabb = primitive.bounds().transformed(primitive.transform());
+                    (num_instances + i as u32, aabb)
+                }),
+        )
+        .collect();
+
+    let mut nodes = Vec::new();
+    bvh::build_bvh(&mut items, 0, 1, &mut nodes);
+
+    let mut gpu_instances: Vec<GpuInstance> = items
+        .iter()
+        .map(|(i, _)| {
+            if *i >= num_instances {
+                let primitive = &geometry.primitives[(*i - num_instances) as usize];
+                let transform = primitive.transform();
+                return GpuInstance {
+                    transform,
+                    inverse_transform: transform.inverse(),
+                    blas_root: 0,
+                    material_index: primitive.material_index,
+                    kind: primitive.gpu_kind(),
+                    pad0: 0,
+                };
+            }
+
+            let instance = &geometry.instances[*i as usize];
+            GpuInstance {
+                transform: instance.transform,
+                inverse_transform: instance.transform.inverse(),
+                blas_root: bottom_levels.roots[instance.mesh as usize],
+                material_index: instance.material.unwrap_or(u32::MAX),
+                kind: 0,
+                pad0: 0,
+            }
+        })
+        .collect();
+
+    if gpu_instances.is_empty() {
+        gpu_instances.push(GpuInstance::default());
+    }
+
+    (gpu_instances, nodes)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuTexture {
+    offset: u32,
+    width: u32,
+    height: u32,
+    srgb: u32,
+}
+
+// packs all the textures into a single buffer, starting with a header per texture followed by
+// the texels. it always has at least one element since empty storage buffers can't be bound
+pub fn pack_textures(textures: Vec<Texture>) -> Vec<u32> {
+    let header_len = textures.len() * std::mem::size_of::<GpuTexture>() / 4;
+    let mut gpu_textures = Vec::with_capacity(textures.len());
+    let mut texels: Vec<u32> = Vec::new();
+
+    for texture in textures {
+        gpu_textures.push(GpuTexture {
+            offset: (header_len + texels.len()) as u32,
+            width: texture.size.x,
+            height: texture.size.y,
+            srgb: texture.srgb.into(),
+        });
+        texels.extend(
+            texture
+                .data
+                .chunks_exact(4)
+                .map(|texel| u32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]])),
+        );
+    }
+
+    let mut data: Vec<u32> = bytemuck::cast_slice(&gpu_textures).to_vec();
+    data.extend(texels);
+    if data.is_empty() {
+        data.push(0);
+    }
+    data
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuLight {
+    position: glam::Vec3,
+    kind: u32,
+    direction: glam::Vec3,
+    cos_inner_angle: f32,
+    intensity: glam::Vec3,
+    cos_outer_angle: f32,
+    edge_u: glam::Vec3,
+    radius: f32,
+    edge_v: glam::Vec3,
+    flags: u32,
+    ies_profile: u32,
+    pad0: [u32; 3],
+}
+
+impl From<Light> for GpuLight {
+    fn from(light: Light) -> Self {
+        let intensity = light.color * light.intensity;
+        let flags = u32::from(light.two_sided) | u32::from(light.visible) << 1;
+        let gpu_light = match light.kind {
+            LightKind::Point { position } => Self {
+                position,
+                kind: 0,
+                intensity,
+                ..Default::default()
+            },
+            LightKind::Spot {
+                position,
+                direction,
+                inner_cone_angle,
+                outer_cone_angle,
+            } => Self {
+                position,
+                kind: 1,
+                direction: direction.normalize(),
+                cos_inner_angle: inner_cone_angle.cos(),
+                intensity,
+                cos_outer_angle: outer_cone_angle.cos(),
+                ..Default::default()
+            },
+            LightKind::Directional {
+                direction,
+                angular_diameter,
+            } => Self {
+                kind: 2,
+                direction: direction.normalize(),
+                intensity,
+                cos_outer_angle: (0.5 * angular_diameter).cos(),
+                ..Default::default()
+            },
+            LightKind::Rect {
+                position,
+                edge_u,
+                edge_v,
+            } => Self {
+                position,
+                kind: 3,
+                direction: edge_u.cross(edge_v).normalize(),
+                intensity,
+                edge_u,
+                edge_v,
+                ..Default::default()
+            },
+            LightKind::Disk {
+                position,
+                normal,
+                radius,
+            } => Self {
+                position,
+                kind: 4,
+                direction: normal.normalize(),
+                intensity,
+                radius,
+                ..Default::default()
+            },
+            LightKind::Sphere { position, radius } => Self {
+                position,
+                kind: 5,
+                intensity,
+                radius,
+                ..Default::default()
+            },
+        };
+
+        if let (Some(ies_profile), LightKind::Point { .. } | LightKind::Spot { .. }) =
+            (light.ies_profile, &light.kind)
+        {
+            // reference frame for the horizontal angles of the profile around the light axis
+            let axis = if let LightKind::Spot { direction, .. } = light.kind {
+                direction.normalize()
+            } else {
+                -glam::Vec3::Y
+            };
+            let reference = if axis.x.abs() < 0.9 {
+                glam::Vec3::X
+            } else {
+                glam::Vec3::Z
+            };
+            let edge_u = (reference - axis * reference.dot(axis)).normalize();
+
+            return Self {
+                direction: axis,
+                edge_u,
+                edge_v: axis.cross(edge_u),
+                flags,
+                ies_profile,
+                ..gpu_light
+            };
+        }
+
+        Self {
+            flags,
+            ies_profile: u32::MAX,
+            ..gpu_light
+        }
+    }
+}
+
+impl GpuLight {
+    const KIND_DIRECTIONAL: u32 = 2;
+    const KIND_TRIANGLE: u32 = 6;
+
+    // emissive triangles are two-sided lights with the radiance of their material
+    fn from_triangle(vertices: [glam::Vec3; 3], radiance: glam::Vec3) -> Self {
+        let [p0, p1, p2] = vertices;
+        Self {
+            position: p0,
+            kind: Self::KIND_TRIANGLE,
+            direction: (p1 - p0).cross(p2 - p0).normalize(),
+            intensity: radiance,
+            edge_u: p1 - p0,
+            edge_v: p2 - p0,
+            flags: 1,
+            ies_profile: u32::MAX,
+            ..Default::default()
+        }
+    }
+
+    // bounds for the light bvh, none for directional lights and lights without power
+    fn bounds(&self) -> Option<LightBounds> {
+        use std::f32::consts::PI;
+
+        let max_intensity = self.intensity.max_element();
+        if max_intensity <= 0.0 {
+            return None;
+        }
+
+        let two_sided = self.flags & 1 != 0;
+        let sides = if two_sided { 2.0 } else { 1.0 };
+        let point = |power, direction, cos_theta_o, cos_theta_e| LightBounds {
+            min: self.position,
+            max: self.position,
+            power,
+            direction,
+            cos_theta_o,
+            cos_theta_e,
+            two_sided: false,
+        };
+
+        let bounds = match self.kind {
+            0 => point(4.0 * PI * max_intensity, glam::Vec3::Z, -1.0, 0.0),
+            // the full intensity is emitted within the inner cone, falling off up to the outer one
+            1 => point(
+                4.0 * PI * max_intensity,
+                self.direction,
+                self.cos_inner_angle,
+                (self.cos_outer_angle.acos() - self.cos_inner_angle.acos()).cos(),
+            ),
+            3 | 6 => {
+                let corners = if self.kind == 3 {
+                    vec![
+                        self.position - 0.5 * (self.edge_u + self.edge_v),
+                        self.position + 0.5 * (self.edge_u - self.edge_v),
+                        self.position + 0.5 * (self.edge_u + self.edge_v),
+                        self.position - 0.5 * (self.edge_u - self.edge_v),
+                    ]
+                } else {
+                    vec![
+                        self.position,
+                        self.position + self.edge_u,
+                        self.position + self.edge_v,
+                    ]
+                };
+                let area = self.edge_u.cross(self.edge_v).length()
+                    * if self.kind == 3 { 1.0 } else { 0.5 };
+
+                LightBounds {
+                    min: corners
+                        .iter()
+                        .copied()
+                        .fold(glam::Vec3::INFINITY, glam::Vec3::min),
+                    max: corners
+                        .iter()
+                        .copied()
+                        .fold(glam::Vec3::NEG_INFINITY, glam::Vec3::max),
+                    power: PI * max_intensity * area * sides,
+                    direction: self.direction,
+                    cos_theta_o: 1.0,
+                    cos_theta_e: 0.0,
+                    two_sided,
+                }
+            }
+            4 => {
+                let extent = self.radius
+                    * (glam::Vec3::ONE - self.direction * self.direction)
+                        .max(glam::Vec3::ZERO)
+                        .powf(0.5);
+                LightBounds {
+                    min: self.position - extent,
+                    max: self.position + extent,
+                    power: PI * max_intensity * PI * self.radius * self.radius * sides,
+                    direction: self.direction,
+                    cos_theta_o: 1.0,
+                    cos_theta_e: 0.0,
+                    two_sided,
+                }
+            }
+            5 => LightBounds {
+                min: self.position - glam::Vec3::splat(self.radius),
+                max: self.position + glam::Vec3::splat(self.radius),
+                power: PI * max_intensity * 4.0 * PI * self.radius * self.radius,
+                direction: glam::Vec3::Z,
+                cos_theta_o: -1.0,
+                cos_theta_e: 0.0,
+                two_sided: false,
+            },
+            _ => return None,
+        };
+        Some(bounds)
+    }
+}
+
+// lights are ordered as directional lights (sampled outside the bvh), other analytic lights and
+// emissive triangles. there is always at least one light since empty storage buffers can't be
+// bound, the padding light has no intensity and is skipped by the shader
+pub fn convert_lights(geometry: &Geometry) -> (Vec<GpuLight>, Vec<GpuLightBvhNode>) {
+    let (mut gpu_lights, analytic_lights): (Vec<GpuLight>, Vec<GpuLight>) = geometry
+        .lights
+        .iter()
+        .cloned()
+        .map(GpuLight::from)
+        .partition(|light| light.kind == GpuLight::KIND_DIRECTIONAL);
+    gpu_lights.extend(analytic_lights);
+
+    let areas = geometry.material_areas();
+    for (material_index, vertices) in geometry.world_triangles() {
+        let material_index = material_index as usize;
+        let radiance = geometry.materials[material_index].emitted_radiance(areas[material_index]);
+        let [p0, p1, p2] = vertices;
+        if radiance.max_element() > 0.0 && (p1 - p0).cross(p2 - p0).length_squared() > 0.0 {
+            gpu_lights.push(GpuLight::from_triangle(vertices, radiance));
+        }
+    }
+
+    if gpu_lights.is_empty() {
+        gpu_lights.push(GpuLight::default());
+    }
+
+    let bounds: Vec<(u32, LightBounds)> = gpu_lights
+        .iter()
+        .enumerate()
+        .filter_map(|(i, light)| light.bounds().map(|bounds| (i as u32, bounds)))
+        .collect();
+    let nodes = light_bvh::build_light_bvh(&bounds);
+
+    (gpu_lights, nodes)
+}
+
+pub const IES_TABLE_SIZE: glam::UVec2 = glam::uvec2(64, 128);
+// the stacked tables have to fit the default texture size limit of 8192
+pub const MAX_IES_PROFILES: usize = 8192 / IES_TABLE_SIZE.y as usize;
+
+// stacks the normalized tables of all profiles vertically, always at least one table
+pub fn build_ies_tables(profiles: &[IesProfile]) -> (glam::UVec2, Vec<f32>) {
+    let num_tables = profiles.len().max(1) as u32;
+    let mut data: Vec<f32> = profiles
+        .iter()
+        .flat_map(|profile| profile.to_table(IES_TABLE_SIZE))
+        .collect();
+    data.resize(
+        (IES_TABLE_SIZE.x * IES_TABLE_SIZE.y * num_tables) as usize,
+        0.0,
+    );
+
+    (
+        glam::uvec2(IES_TABLE_SIZE.x, IES_TABLE_SIZE.y * num_tables),
+        data,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::geometry::LightKind;
+
+    fn light(kind: LightKind) -> GpuLight {
+        GpuLight::from(Light {
+            kind,
+            color: glam::Vec3::ONE,
+            intensity: 2.0,
+            visible: true,
+            two_sided: false,
+            ies_profile: None,
+        })
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn spot_light_bounds() {
+        let position = glam::vec3(1.0, 2.0, 3.0);
+        let bounds = light(LightKind::Spot {
+            position,
+            direction: glam::Vec3::NEG_Y,
+            inner_cone_angle: 0.2,
+            outer_cone_angle: 0.6,
+        })
+        .bounds()
+        .unwrap();
+
+        assert_eq!(bounds.min, position);
+        assert_eq!(bounds.max, position);
+        assert_close(bounds.power, 4.0 * PI * 2.0);
+        assert_eq!(bounds.direction, glam::Vec3::NEG_Y);
+        // full intensity within the inner cone, falling off over the rest of the outer one
+        assert_close(bounds.cos_theta_o, 0.2f32.cos());
+        assert_close(bounds.cos_theta_e, 0.4f32.cos());
+    }
+
+    #[test]
+    fn rect_light_bounds() {
+        let bounds = light(LightKind::Rect {
+            position: glam::vec3(0.0, 1.0, 0.0),
+            edge_u: glam::vec3(2.0, 0.0, 0.0),
+            edge_v: glam::vec3(0.0, 0.0, 4.0),
+        })
+        .bounds()
+        .unwrap();
+
+        assert_eq!(bounds.min, glam::vec3(-1.0, 1.0, -2.0));
+        assert_eq!(bounds.max, glam::vec3(1.0, 1.0, 2.0));
+        assert_close(bounds.power, PI * 2.0 * 8.0);
+        assert_eq!(bounds.direction, glam::Vec3::NEG_Y);
+        assert_eq!((bounds.cos_theta_o, bounds.cos_theta_e), (1.0, 0.0));
+        assert!(!bounds.two_sided);
+    }
+
+    #[test]
+    fn emissive_triangle_bounds() {
+        let vertices = [
+            glam::vec3(0.0, 0.0, 0.0),
+            glam::vec3(2.0, 0.0, 0.0),
+            glam::vec3(0.0, 3.0, 0.0),
+        ];
+        let bounds = GpuLight::from_triangle(vertices, glam::vec3(1.0, 0.5, 0.0))
+            .bounds()
+            .unwrap();
+
+        assert_eq!(bounds.min, glam::Vec3::ZERO);
+        assert_eq!(bounds.max, glam::vec3(2.0, 3.0, 0.0));
+        // both sides of an area of 3 emit
+        assert_close(bounds.power, PI * 3.0 * 2.0);
+        assert_eq!(bounds.direction, glam::Vec3::Z);
+        assert!(bounds.two_sided);
+    }
+
+    #[test]
+    fn directional_lights_are_unbounded() {
+        let directional = light(LightKind::Directional {
+            direction: glam::Vec3::NEG_Y,
+            angular_diameter: 0.01,
+        });
+        assert!(directional.bounds().is_none());
+    }
+}
@@ -2,179 +2,569 @@ use std::collections::HashMap;
 
 use glam::Vec4Swizzles;
 
-use crate::geometry::{Geometry, Material, Triangle, Vertex};
+use crate::{
+    camera::{Camera, Projection},
+    geometry::{
+        mesh_data::{append_mesh_data, MeshData},
+        AlphaMode, EmissionUnit, Geometry, Instance, Light, LightKind, Material, Mesh, Texture,
+        Triangle, Vertex,
+    },
+};
 
-pub fn load(path: &str, name: &str) -> Result<Geometry, Box<dyn std::error::Error>> {
-    let (document, buffers, _) = gltf::import(path)?;
+// part of a gltf document to load
+#[derive(Clone, Copy, Debug)]
+pub enum GltfSelection<'a> {
+    // the scene marked as default by the document, or the first one
+    DefaultScene,
+    SceneIndex(usize),
+    SceneName(&'a str),
+    // a node and its children, placed with their transform in the scene containing them
+    Node(&'a str),
+}
 
-    if let Some(scene) = document
-        .scenes()
-        .find(|scene| scene.name().is_some_and(|n| n == name))
-    {
-        let mut materials = vec![Material {
+// cameras without an aspect ratio use 1.0 and are expected to be fitted to the viewport
+#[derive(Clone, Debug)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub camera: Camera,
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfSceneInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub is_default: bool,
+    pub nodes: Vec<GltfNodeInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfNodeInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub has_mesh: bool,
+    pub has_light: bool,
+    pub has_camera: bool,
+    pub children: Vec<GltfNodeInfo>,
+}
+
+pub fn load(
+    path: &str,
+    selection: GltfSelection,
+) -> Result<(Geometry, Vec<GltfCamera>), GltfError> {
+    let (document, buffers, images) = gltf::import(path)?;
+
+    let roots = match selection {
+        GltfSelection::DefaultScene => {
+            let scene = document
+                .default_scene()
+                .or_else(|| document.scenes().next())
+                .ok_or(GltfError::NoScenes)?;
+            scene_roots(&scene)
+        }
+        GltfSelection::SceneIndex(index) => {
+            let scene = document
+                .scenes()
+                .nth(index)
+                .ok_or(GltfError::SceneIndexOutOfRange(index))?;
+            scene_roots(&scene)
+        }
+        GltfSelection::SceneName(name) => {
+            let scene = document
+                .scenes()
+                .find(|scene| scene.name() == Some(name))
+                .ok_or_else(|| GltfError::SceneNotFound(name.to_owned()))?;
+            scene_roots(&scene)
+        }
+        GltfSelection::Node(name) => {
+            let node = document
+                .nodes()
+                .find(|node| node.name() == Some(name))
+                .ok_or_else(|| GltfError::NodeNotFound(name.to_owned()))?;
+            let parent_transform = document
+                .scenes()
+                .flat_map(|scene| scene.nodes())
+                .find_map(|root| parent_transform(&root, node.index(), glam::Mat4::IDENTITY))
+                .unwrap_or(glam::Mat4::IDENTITY);
+            vec![(node, parent_transform)]
+        }
+    };
+
+    let mut context = LoadContext {
+        buffers: &buffers,
+        images: &images,
+        textures: Vec::new(),
+        textures_map: HashMap::new(),
+        materials: vec![Material {
             albedo: glam::vec3(1.0, 0.0, 1.0),
             roughness: 0.0,
             metallic: 0.0,
             emission: glam::Vec3::ZERO,
-        }];
-        let mut materials_map: HashMap<usize, u32> = HashMap::new();
-        let mut vertices: Vec<Vertex> = Vec::new();
-        let mut triangles: Vec<Triangle> = Vec::new();
-
-        let transform_matrix = glam::Mat4::IDENTITY;
-        for node in scene.nodes() {
-            handle_node(
-                &node,
-                &buffers,
-                &mut materials,
-                &mut materials_map,
-                &mut vertices,
-                &mut triangles,
-                transform_matrix,
-            );
-        }
+            ..Default::default()
+        }],
+        materials_map: HashMap::new(),
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+        meshes: Vec::new(),
+        meshes_map: HashMap::new(),
+        instances: Vec::new(),
+        lights: Vec::new(),
+        cameras: Vec::new(),
+    };
 
-        Ok(Geometry {
-            materials,
-            vertices,
-            triangles,
+    for (node, transform_matrix) in roots {
+        handle_node(&node, &mut context, transform_matrix);
+    }
+
+    let geometry = Geometry {
+        textures: context.textures,
+        materials: context.materials,
+        vertices: context.vertices,
+        triangles: context.triangles,
+        meshes: context.meshes,
+        instances: context.instances,
+        primitives: Vec::new(),
+        lights: context.lights,
+        ies_profiles: Vec::new(),
+    };
+    Ok((geometry, context.cameras))
+}
+
+// reads only the json part of the document, buffers and images aren't loaded
+pub fn list_scenes(path: &str) -> Result<Vec<GltfSceneInfo>, GltfError> {
+    let document = gltf::Gltf::open(path)?.document;
+    let default_index = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.index());
+
+    Ok(document
+        .scenes()
+        .map(|scene| GltfSceneInfo {
+            index: scene.index(),
+            name: scene.name().map(str::to_owned),
+            is_default: Some(scene.index()) == default_index,
+            nodes: scene.nodes().map(|node| node_info(&node)).collect(),
         })
-    } else {
-        Err(Box::new(SceneNotFoundError))
+        .collect())
+}
+
+fn node_info(node: &gltf::Node) -> GltfNodeInfo {
+    GltfNodeInfo {
+        index: node.index(),
+        name: node.name().map(str::to_owned),
+        has_mesh: node.mesh().is_some(),
+        has_light: node.light().is_some(),
+        has_camera: node.camera().is_some(),
+        children: node.children().map(|child| node_info(&child)).collect(),
     }
 }
 
+fn scene_roots<'a>(scene: &gltf::Scene<'a>) -> Vec<(gltf::Node<'a>, glam::Mat4)> {
+    scene
+        .nodes()
+        .map(|node| (node, glam::Mat4::IDENTITY))
+        .collect()
+}
+
+// world transform of the parent of `target` if it is below `node`
+fn parent_transform(node: &gltf::Node, target: usize, transform: glam::Mat4) -> Option<glam::Mat4> {
+    if node.index() == target {
+        return Some(transform);
+    }
+
+    let transform = transform * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+    node.children()
+        .find_map(|child| parent_transform(&child, target, transform))
+}
+
 #[derive(Debug)]
-pub struct SceneNotFoundError;
+pub enum GltfError {
+    Import(gltf::Error),
+    NoScenes,
+    SceneIndexOutOfRange(usize),
+    SceneNotFound(String),
+    NodeNotFound(String),
+}
 
-impl std::fmt::Display for SceneNotFoundError {
+impl std::fmt::Display for GltfError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "SceneNotFoundError")
+        match self {
+            GltfError::Import(err) => write!(f, "failed to import gltf: {}", err),
+            GltfError::NoScenes => write!(f, "the document has no scenes"),
+            GltfError::SceneIndexOutOfRange(index) => write!(f, "scene {} not found", index),
+            GltfError::SceneNotFound(name) => write!(f, "scene {:?} not found", name),
+            GltfError::NodeNotFound(name) => write!(f, "node {:?} not found", name),
+        }
     }
 }
 
-impl std::error::Error for SceneNotFoundError {}
+impl std::error::Error for GltfError {}
+
+impl From<gltf::Error> for GltfError {
+    fn from(err: gltf::Error) -> Self {
+        GltfError::Import(err)
+    }
+}
+
+struct LoadContext<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    textures: Vec<Texture>,
+    textures_map: HashMap<(usize, bool), Option<u32>>,
+    materials: Vec<Material>,
+    materials_map: HashMap<usize, u32>,
+    vertices: Vec<Vertex>,
+    triangles: Vec<Triangle>,
+    meshes: Vec<Mesh>,
+    meshes_map: HashMap<usize, u32>,
+    instances: Vec<Instance>,
+    lights: Vec<Light>,
+    cameras: Vec<GltfCamera>,
+}
 
 fn iterate_children(
     nodes: gltf::scene::iter::Children,
-    buffers: &[gltf::buffer::Data],
-    materials: &mut Vec<Material>,
-    materials_map: &mut HashMap<usize, u32>,
-    vertices: &mut Vec<Vertex>,
-    triangles: &mut Vec<Triangle>,
+    context: &mut LoadContext,
     transform_matrix: glam::Mat4,
 ) {
     for node in nodes {
-        handle_node(
-            &node,
-            buffers,
-            materials,
-            materials_map,
-            vertices,
-            triangles,
-            transform_matrix,
-        );
+        handle_node(&node, context, transform_matrix);
     }
 }
 
-fn handle_node(
-    node: &gltf::Node,
-    buffers: &[gltf::buffer::Data],
-    materials: &mut Vec<Material>,
-    materials_map: &mut HashMap<usize, u32>,
-    vertices: &mut Vec<Vertex>,
-    triangles: &mut Vec<Triangle>,
-    mut transform_matrix: glam::Mat4,
-) {
+fn handle_node(node: &gltf::Node, context: &mut LoadContext, mut transform_matrix: glam::Mat4) {
     transform_matrix *= glam::Mat4::from_cols_array_2d(&node.transform().matrix());
 
+    if let Some(gltf_light) = node.light() {
+        context
+            .lights
+            .push(convert_light(&gltf_light, transform_matrix));
+    }
+
+    if let Some(gltf_camera) = node.camera() {
+        context.cameras.push(GltfCamera {
+            name: gltf_camera.name().or(node.name()).map(str::to_owned),
+            camera: convert_camera(&gltf_camera, transform_matrix),
+        });
+    }
+
     if let Some(gltf_mesh) = node.mesh() {
-        for prim in gltf_mesh
-            .primitives()
-            .filter(|prim| prim.mode() == gltf::mesh::Mode::Triangles)
-        {
-            let reader = prim.reader(|buffer| Some(&buffers[buffer.index()]));
-
-            let mut indices = Vec::new();
-            if let Some(gltf::mesh::util::ReadIndices::U16(gltf::accessor::Iter::Standard(iter))) =
-                reader.read_indices()
-            {
-                for v in iter {
-                    indices.push(v as u32);
-                }
+        // meshes are loaded once in their local space and shared by every node that uses them
+        let mesh = match context.meshes_map.get(&gltf_mesh.index()) {
+            Some(mesh) => *mesh,
+            None => {
+                let mesh = load_mesh(&gltf_mesh, context);
+                context.meshes_map.insert(gltf_mesh.index(), mesh);
+                mesh
             }
+        };
+        context.instances.push(Instance {
+            mesh,
+            transform: transform_matrix,
+            material: None,
+            name: node.name().map(str::to_owned),
+        });
+    }
 
-            let mut positions = Vec::new();
-            if let Some(iter) = reader.read_positions() {
-                for v in iter {
-                    positions.push(v);
-                }
-            }
+    iterate_children(node.children(), context, transform_matrix);
+}
 
-            let mut tex_coords = Vec::new();
-            if let Some(gltf::mesh::util::ReadTexCoords::F32(gltf::accessor::Iter::Standard(
-                iter,
-            ))) = reader.read_tex_coords(0)
-            {
-                for v in iter {
-                    tex_coords.push(v);
-                }
-            }
+fn load_mesh(gltf_mesh: &gltf::Mesh, context: &mut LoadContext) -> u32 {
+    let first_triangle = context.triangles.len() as u32;
+    for prim in gltf_mesh
+        .primitives()
+        .filter(|prim| prim.mode() == gltf::mesh::Mode::Triangles)
+    {
+        let reader = prim.reader(|buffer| Some(&context.buffers[buffer.index()]));
 
-            let mut normals = Vec::new();
-            if let Some(iter) = reader.read_normals() {
-                for v in iter {
-                    normals.push(v);
-                }
-            }
+        // sparse accessors without a buffer view iterate forever, so every read is capped at
+        // the accessor count
+        let Some(num_vertices) = prim
+            .get(&gltf::Semantic::Positions)
+            .map(|accessor| accessor.count())
+        else {
+            continue;
+        };
 
-            let index_offset = vertices.len() as u32;
+        let positions: Vec<glam::Vec3> = reader
+            .read_positions()
+            .into_iter()
+            .flatten()
+            .take(num_vertices)
+            .map(glam::Vec3::from)
+            .collect();
 
-            for i in 0..positions.len() {
-                vertices.push(Vertex {
-                    position: transform_matrix.transform_point3(positions[i].into()),
-                    tex_coord: glam::Vec2::from_slice(tex_coords.get(i).unwrap_or(&[0.0, 0.0])),
-                    normal: normals[i].into(),
-                });
-            }
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices
+                .into_u32()
+                .take(prim.indices().map_or(0, |accessor| accessor.count()))
+                .collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
 
-            let material_index = if let Some(gltf_mat_idx) = prim.material().index() {
-                *materials_map.entry(gltf_mat_idx).or_insert_with(|| {
-                    let pbr_metallic_roughness = prim.material().pbr_metallic_roughness();
-                    let material_index = materials.len();
-                    materials.push(Material {
-                        albedo: glam::Vec4::from(pbr_metallic_roughness.base_color_factor()).xyz(),
-                        roughness: pbr_metallic_roughness.roughness_factor(),
-                        metallic: pbr_metallic_roughness.metallic_factor(),
-                        emission: prim.material().emissive_factor().into(),
-                    });
-                    material_index as u32
+        let read_tex_coords = |set: u32| -> Vec<glam::Vec2> {
+            reader
+                .read_tex_coords(set)
+                .map(|tex_coords| {
+                    tex_coords
+                        .into_f32()
+                        .take(num_vertices)
+                        .map(glam::Vec2::from)
+                        .collect()
                 })
+                .unwrap_or_default()
+        };
+        let tex_coords = read_tex_coords(0);
+        let tex_coords_1 = read_tex_coords(1);
+
+        let tangents: Vec<glam::Vec4> = reader
+            .read_tangents()
+            .into_iter()
+            .flatten()
+            .take(num_vertices)
+            .map(glam::Vec4::from)
+            .collect();
+
+        // COLOR_0 is linear, rgb colors get an alpha of one
+        let colors: Vec<glam::Vec4> = reader
+            .read_colors(0)
+            .map(|colors| {
+                colors
+                    .into_rgba_f32()
+                    .take(num_vertices)
+                    .map(glam::Vec4::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let normals: Vec<glam::Vec3> = reader
+            .read_normals()
+            .into_iter()
+            .flatten()
+            .take(num_vertices)
+            .map(|normal| glam::Vec3::from(normal).normalize_or_zero())
+            .collect();
+
+        let material_index = if let Some(gltf_mat_idx) = prim.material().index() {
+            if let Some(material_index) = context.materials_map.get(&gltf_mat_idx) {
+                *material_index
             } else {
-                0
-            };
-
-            let mut it = indices.iter().peekable();
-            while it.peek().is_some() {
-                let i0 = *it.next().unwrap();
-                let i1 = *it.next().unwrap();
-                let i2 = *it.next().unwrap();
-
-                triangles.push(Triangle {
-                    vertex_indices: [index_offset + i0, index_offset + i1, index_offset + i2],
-                    material_index,
-                });
+                let material = convert_material(&prim.material(), context);
+                let material_index = context.materials.len() as u32;
+                context.materials.push(material);
+                context.materials_map.insert(gltf_mat_idx, material_index);
+                material_index
             }
-        }
+        } else {
+            0
+        };
+
+        append_mesh_data(
+            MeshData {
+                positions: &positions,
+                normals: &normals,
+                tex_coords: &tex_coords,
+                tex_coords_1: &tex_coords_1,
+                tangents: &tangents,
+                colors: &colors,
+                indices: &indices,
+                material_index,
+            },
+            &mut context.vertices,
+            &mut context.triangles,
+        );
+    }
+
+    context.meshes.push(Mesh {
+        first_triangle,
+        num_triangles: context.triangles.len() as u32 - first_triangle,
+    });
+    context.meshes.len() as u32 - 1
+}
+
+fn convert_material(gltf_material: &gltf::Material, context: &mut LoadContext) -> Material {
+    let pbr_metallic_roughness = gltf_material.pbr_metallic_roughness();
+    let transmission = gltf_material.transmission();
+    let volume = gltf_material.volume();
+    let specular = gltf_material.specular();
+    // KHR_materials_clearcoat and KHR_materials_sheen aren't supported by the gltf crate yet
+    let clearcoat = gltf_material.extension_value("KHR_materials_clearcoat");
+    let sheen = gltf_material.extension_value("KHR_materials_sheen");
+
+    Material {
+        albedo: glam::Vec4::from(pbr_metallic_roughness.base_color_factor()).xyz(),
+        roughness: pbr_metallic_roughness.roughness_factor(),
+        metallic: pbr_metallic_roughness.metallic_factor(),
+        emission: gltf_material.emissive_factor().into(),
+        emission_strength: gltf_material.emissive_strength().unwrap_or(1.0),
+        emission_unit: EmissionUnit::Radiance,
+        ior: gltf_material.ior().unwrap_or(1.5),
+        transmission: transmission.map_or(0.0, |t| t.transmission_factor()),
+        // without KHR_materials_volume (or with a zero thickness) the surface is thin-walled
+        thin_walled: !volume.as_ref().is_some_and(|v| v.thickness_factor() > 0.0),
+        attenuation_color: volume
+            .as_ref()
+            .map_or(glam::Vec3::ONE, |v| v.attenuation_color().into()),
+        attenuation_distance: volume
+            .as_ref()
+            .map_or(f32::INFINITY, |v| v.attenuation_distance()),
+        clearcoat: extension_f32(clearcoat, "clearcoatFactor").unwrap_or(0.0),
+        clearcoat_roughness: extension_f32(clearcoat, "clearcoatRoughnessFactor").unwrap_or(0.0),
+        sheen_color: extension_vec3(sheen, "sheenColorFactor").unwrap_or(glam::Vec3::ZERO),
+        sheen_roughness: extension_f32(sheen, "sheenRoughnessFactor").unwrap_or(0.0),
+        specular: specular.as_ref().map_or(1.0, |s| s.specular_factor()),
+        specular_color: specular
+            .as_ref()
+            .map_or(glam::Vec3::ONE, |s| s.specular_color_factor().into()),
+        alpha: pbr_metallic_roughness.base_color_factor()[3],
+        alpha_mode: match gltf_material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: gltf_material.alpha_cutoff().unwrap_or(0.5),
+        base_color_texture: pbr_metallic_roughness
+            .base_color_texture()
+            .and_then(|info| load_texture(&info.texture(), true, context)),
+        base_color_tex_coord: pbr_metallic_roughness
+            .base_color_texture()
+            .map_or(0, |info| info.tex_coord()),
+        normal_texture: gltf_material
+            .normal_texture()
+            .and_then(|info| load_texture(&info.texture(), false, context)),
+        normal_tex_coord: gltf_material
+            .normal_texture()
+            .map_or(0, |info| info.tex_coord()),
+        normal_scale: gltf_material
+            .normal_texture()
+            .map_or(1.0, |info| info.scale()),
+        double_sided: gltf_material.double_sided(),
+    }
+}
+
+fn convert_light(gltf_light: &gltf::khr_lights_punctual::Light, transform: glam::Mat4) -> Light {
+    use gltf::khr_lights_punctual::Kind;
+
+    // glTF lights are located at the node origin and point down its -Z axis
+    let position = transform.transform_point3(glam::Vec3::ZERO);
+    let direction = transform.transform_vector3(-glam::Vec3::Z).normalize();
+
+    let kind = match gltf_light.kind() {
+        Kind::Point => LightKind::Point { position },
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => LightKind::Spot {
+            position,
+            direction,
+            inner_cone_angle,
+            outer_cone_angle,
+        },
+        Kind::Directional => LightKind::Directional {
+            direction,
+            angular_diameter: 0.0,
+        },
+    };
+
+    Light {
+        kind,
+        color: gltf_light.color().into(),
+        intensity: gltf_light.intensity(),
+        ..Default::default()
     }
+}
+
+fn convert_camera(gltf_camera: &gltf::Camera, transform: glam::Mat4) -> Camera {
+    use gltf::camera::Projection as GltfProjection;
+
+    // glTF cameras are located at the node origin and look down its -Z axis with +Y up
+    let position = transform.transform_point3(glam::Vec3::ZERO);
+    let forward = transform.transform_vector3(-glam::Vec3::Z).normalize();
+    let up = transform.transform_vector3(glam::Vec3::Y).normalize();
+
+    match gltf_camera.projection() {
+        GltfProjection::Perspective(perspective) => Camera {
+            position,
+            forward,
+            up,
+            aspect: perspective.aspect_ratio().unwrap_or(1.0),
+            fovy: perspective.yfov().to_degrees(),
+            znear: perspective.znear(),
+            zfar: perspective.zfar().unwrap_or(f32::INFINITY),
+            projection: Projection::Perspective,
+        },
+        GltfProjection::Orthographic(orthographic) => Camera {
+            position,
+            forward,
+            up,
+            aspect: orthographic.xmag() / orthographic.ymag(),
+            znear: orthographic.znear(),
+            zfar: orthographic.zfar(),
+            projection: Projection::Orthographic {
+                half_height: orthographic.ymag(),
+            },
+            ..Default::default()
+        },
+    }
+}
+
+fn load_texture(texture: &gltf::Texture, srgb: bool, context: &mut LoadContext) -> Option<u32> {
+    let image_index = texture.source().index();
+    if let Some(texture_index) = context.textures_map.get(&(image_index, srgb)) {
+        return *texture_index;
+    }
+
+    let texture_index = convert_image(&context.images[image_index], srgb).map(|texture| {
+        context.textures.push(texture);
+        context.textures.len() as u32 - 1
+    });
+    context
+        .textures_map
+        .insert((image_index, srgb), texture_index);
+    texture_index
+}
+
+fn convert_image(image: &gltf::image::Data, srgb: bool) -> Option<Texture> {
+    use gltf::image::Format;
+
+    let data = match image.format {
+        Format::R8 => image.pixels.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+        // two channel images are decoded from grayscale images with alpha
+        Format::R8G8 => image
+            .pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        Format::R8G8B8 => image
+            .pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        Format::R8G8B8A8 => image.pixels.clone(),
+        _ => {
+            log::warn!("unsupported texture format {:?}", image.format);
+            return None;
+        }
+    };
+
+    Some(Texture {
+        size: glam::uvec2(image.width, image.height),
+        data,
+        srgb,
+    })
+}
+
+fn extension_f32(extension: Option<&gltf::json::Value>, key: &str) -> Option<f32> {
+    extension?.get(key)?.as_f64().map(|v| v as f32)
+}
 
-    iterate_children(
-        node.children(),
-        buffers,
-        materials,
-        materials_map,
-        vertices,
-        triangles,
-        transform_matrix,
-    );
+fn extension_vec3(extension: Option<&gltf::json::Value>, key: &str) -> Option<glam::Vec3> {
+    let values = extension?.get(key)?.as_array()?;
+    let values: Option<Vec<f32>> = values
+        .iter()
+        .map(|v| v.as_f64().map(|v| v as f32))
+        .collect();
+    values
+        .filter(|v| v.len() == 3)
+        .map(|v| glam::Vec3::from_slice(&v))
 }
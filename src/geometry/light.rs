@@ -0,0 +1,145 @@
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: glam::Vec3,
+    // candela for point and spot lights, lux for directional lights, nits for area lights
+    pub intensity: f32,
+    // area lights only: whether camera rays see the light and whether rects and disks emit
+    // from their back side too
+    pub visible: bool,
+    pub two_sided: bool,
+    // point and spot lights only: index into `Geometry::ies_profiles` scaling the intensity by
+    // the normalized profile, the nadir of point lights points down (-Y)
+    pub ies_profile: Option<u32>,
+}
+
+// angles are in radians, directions point where the light travels
+#[derive(Clone, Debug)]
+pub enum LightKind {
+    Point {
+        position: glam::Vec3,
+    },
+    Spot {
+        position: glam::Vec3,
+        direction: glam::Vec3,
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+    Directional {
+        direction: glam::Vec3,
+        angular_diameter: f32,
+    },
+    // spans position +- edge_u / 2 +- edge_v / 2 and emits towards edge_u x edge_v
+    Rect {
+        position: glam::Vec3,
+        edge_u: glam::Vec3,
+        edge_v: glam::Vec3,
+    },
+    Disk {
+        position: glam::Vec3,
+        normal: glam::Vec3,
+        radius: f32,
+    },
+    Sphere {
+        position: glam::Vec3,
+        radius: f32,
+    },
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point {
+                position: glam::Vec3::ZERO,
+            },
+            color: glam::Vec3::ONE,
+            intensity: 1.0,
+            visible: true,
+            two_sided: false,
+            ies_profile: None,
+        }
+    }
+}
+
+impl Light {
+    // returns the reason the light is invalid
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self.kind {
+            LightKind::Point { position } | LightKind::Sphere { position, .. }
+                if !position.is_finite() =>
+            {
+                return Err("position must be finite");
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                if !position.is_finite() {
+                    return Err("position must be finite");
+                }
+                if direction.length_squared() == 0.0 {
+                    return Err("direction must not be zero");
+                }
+                if inner_cone_angle < 0.0 || inner_cone_angle > outer_cone_angle {
+                    return Err("cone angles must satisfy 0 <= inner <= outer");
+                }
+            }
+            LightKind::Directional {
+                direction,
+                angular_diameter,
+            } => {
+                if direction.length_squared() == 0.0 {
+                    return Err("direction must not be zero");
+                }
+                if angular_diameter < 0.0 {
+                    return Err("angular diameter must not be negative");
+                }
+            }
+            LightKind::Rect {
+                position,
+                edge_u,
+                edge_v,
+            } => {
+                if !position.is_finite() {
+                    return Err("position must be finite");
+                }
+                if edge_u.cross(edge_v).length_squared() == 0.0 {
+                    return Err("edges must not be degenerate");
+                }
+                // the edges have to be perpendicular for spherical rectangle sampling
+                if edge_u.dot(edge_v).abs() > 1e-4 * edge_u.length() * edge_v.length() {
+                    return Err("edges must be perpendicular");
+                }
+            }
+            LightKind::Disk {
+                position,
+                normal,
+                radius,
+            } => {
+                if !position.is_finite() {
+                    return Err("position must be finite");
+                }
+                if normal.length_squared() == 0.0 {
+                    return Err("normal must not be zero");
+                }
+                if radius <= 0.0 {
+                    return Err("radius must be positive");
+                }
+            }
+            LightKind::Sphere { radius, .. } if radius <= 0.0 => {
+                return Err("radius must be positive");
+            }
+            _ => {}
+        }
+
+        if !self.color.is_finite() {
+            return Err("color must be finite");
+        }
+        if self.intensity.is_nan() || self.intensity < 0.0 {
+            return Err("intensity must not be negative");
+        }
+        Ok(())
+    }
+}
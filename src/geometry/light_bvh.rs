@@ -0,0 +1,352 @@
+use std::f32::consts::PI;
+
+// bounds of the emitters below a node: their positions, total power and the cone of emitted
+// directions (normals within cos_theta_o of `direction`, emitting up to cos_theta_e away from
+// them), as in pbrt-v4
+#[derive(Clone, Copy, Debug)]
+pub(super) struct LightBounds {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+    pub power: f32,
+    pub direction: glam::Vec3,
+    pub cos_theta_o: f32,
+    pub cos_theta_e: f32,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    fn centroid(&self) -> glam::Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.x * d.z + d.y * d.z)
+    }
+
+    fn union(&self, other: &LightBounds) -> LightBounds {
+        let (direction, cos_theta_o) = cone_union(
+            (self.direction, self.cos_theta_o),
+            (other.direction, other.cos_theta_o),
+        );
+
+        LightBounds {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            power: self.power + other.power,
+            direction,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    // surface area orientation heuristic
+    fn cost(&self, total_diagonal: glam::Vec3, dim: usize) -> f32 {
+        let theta_o = self.cos_theta_o.clamp(-1.0, 1.0).acos();
+        let theta_e = self.cos_theta_e.clamp(-1.0, 1.0).acos();
+        let theta_w = (theta_o + theta_e).min(PI);
+        let sin_theta_o = (1.0 - self.cos_theta_o * self.cos_theta_o).max(0.0).sqrt();
+        let m_omega = 2.0 * PI * (1.0 - self.cos_theta_o)
+            + PI / 2.0
+                * (2.0 * theta_w * sin_theta_o
+                    - (theta_o - 2.0 * theta_w).cos()
+                    - 2.0 * theta_o * sin_theta_o
+                    + self.cos_theta_o);
+        let kr = total_diagonal.max_element() / total_diagonal[dim];
+        self.power * m_omega * kr * self.surface_area()
+    }
+}
+
+fn cone_union(a: (glam::Vec3, f32), b: (glam::Vec3, f32)) -> (glam::Vec3, f32) {
+    let theta_a = a.1.clamp(-1.0, 1.0).acos();
+    let theta_b = b.1.clamp(-1.0, 1.0).acos();
+    let theta_d = a.0.angle_between(b.0);
+
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+    let rotation_axis = a.0.cross(b.0);
+    if theta_o >= PI || rotation_axis.length_squared() == 0.0 {
+        return (glam::Vec3::Z, -1.0);
+    }
+
+    let rotation = glam::Quat::from_axis_angle(rotation_axis.normalize(), theta_o - theta_a);
+    (rotation * a.0, theta_o.cos())
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuLightBvhNode {
+    bounds_min: glam::Vec3,
+    power: f32,
+    bounds_max: glam::Vec3,
+    cos_theta_o: f32,
+    direction: glam::Vec3,
+    cos_theta_e: f32,
+    // light index for leaves, index of the second child otherwise (the first one follows the node)
+    index: u32,
+    flags: u32,
+    pad0: [u32; 2],
+}
+
+impl GpuLightBvhNode {
+    const FLAG_LEAF: u32 = 1;
+    const FLAG_TWO_SIDED: u32 = 2;
+
+    fn new(bounds: &LightBounds, index: u32, leaf: bool) -> Self {
+        Self {
+            bounds_min: bounds.min,
+            power: bounds.power,
+            bounds_max: bounds.max,
+            cos_theta_o: bounds.cos_theta_o,
+            direction: bounds.direction,
+            cos_theta_e: bounds.cos_theta_e,
+            index,
+            flags: if leaf { Self::FLAG_LEAF } else { 0 }
+                | if bounds.two_sided {
+                    Self::FLAG_TWO_SIDED
+                } else {
+                    0
+                },
+            pad0: [0; 2],
+        }
+    }
+}
+
+// builds a light bvh with one light per leaf in depth first order, an empty tree is a single
+// node without power
+pub(super) fn build_light_bvh(lights: &[(u32, LightBounds)]) -> Vec<GpuLightBvhNode> {
+    let mut nodes = Vec::new();
+    if lights.is_empty() {
+        nodes.push(GpuLightBvhNode::default());
+        return nodes;
+    }
+
+    let mut lights = lights.to_vec();
+    build_recursive(&mut lights, &mut nodes);
+    nodes
+}
+
+fn build_recursive(
+    lights: &mut [(u32, LightBounds)],
+    nodes: &mut Vec<GpuLightBvhNode>,
+) -> LightBounds {
+    if lights.len() == 1 {
+        let (light_index, bounds) = lights[0];
+        nodes.push(GpuLightBvhNode::new(&bounds, light_index, true));
+        return bounds;
+    }
+
+    let mid = find_split(lights);
+
+    let node_index = nodes.len();
+    nodes.push(GpuLightBvhNode::default());
+
+    let left_bounds = build_recursive(&mut lights[..mid], nodes);
+    let second_child = nodes.len() as u32;
+    let right_bounds = build_recursive(&mut lights[mid..], nodes);
+
+    let bounds = left_bounds.union(&right_bounds);
+    nodes[node_index] = GpuLightBvhNode::new(&bounds, second_child, false);
+    bounds
+}
+
+// sorts the lights along the best split axis and returns the split position
+fn find_split(lights: &mut [(u32, LightBounds)]) -> usize {
+    const NUM_BUCKETS: usize = 12;
+
+    let (centroid_min, centroid_max) = lights.iter().fold(
+        (glam::Vec3::INFINITY, glam::Vec3::NEG_INFINITY),
+        |(min, max), (_, bounds)| (min.min(bounds.centroid()), max.max(bounds.centroid())),
+    );
+    let total_diagonal = lights
+        .iter()
+        .skip(1)
+        .fold(lights[0].1, |acc, (_, bounds)| acc.union(bounds));
+    let total_diagonal = (total_diagonal.max - total_diagonal.min).max(glam::Vec3::splat(1e-6));
+
+    let bucket = |bounds: &LightBounds, dim: usize| {
+        let extent = centroid_max[dim] - centroid_min[dim];
+        let offset = (bounds.centroid()[dim] - centroid_min[dim]) / extent;
+        ((offset * NUM_BUCKETS as f32) as usize).min(NUM_BUCKETS - 1)
+    };
+
+    let mut best: Option<(f32, usize, usize)> = None;
+    for dim in 0..3 {
+        if centroid_max[dim] <= centroid_min[dim] {
+            continue;
+        }
+
+        let mut buckets: [Option<LightBounds>; NUM_BUCKETS] = [None; NUM_BUCKETS];
+        for (_, bounds) in lights.iter() {
+            let b = bucket(bounds, dim);
+            buckets[b] = Some(buckets[b].map_or(*bounds, |acc| acc.union(bounds)));
+        }
+
+        let union_of = |buckets: &[Option<LightBounds>]| {
+            buckets
+                .iter()
+                .flatten()
+                .fold(None, |acc: Option<LightBounds>, bounds| {
+                    Some(acc.map_or(*bounds, |acc| acc.union(bounds)))
+                })
+        };
+
+        for split in 0..NUM_BUCKETS - 1 {
+            let (Some(left), Some(right)) = (
+                union_of(&buckets[..=split]),
+                union_of(&buckets[split + 1..]),
+            ) else {
+                continue;
+            };
+
+            let cost = left.cost(total_diagonal, dim) + right.cost(total_diagonal, dim);
+            if !best.is_some_and(|(best_cost, _, _)| cost >= best_cost) {
+                best = Some((cost, dim, split));
+            }
+        }
+    }
+
+    let Some((_, dim, split)) = best else {
+        // all the centroids coincide
+        return lights.len() / 2;
+    };
+
+    lights.sort_by(|a, b| a.1.centroid()[dim].total_cmp(&b.1.centroid()[dim]));
+    let mid = lights.partition_point(|(_, bounds)| bucket(bounds, dim) <= split);
+    mid.clamp(1, lights.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cone `outer` contains cone `inner`, a cosine of -1 covers every direction
+    fn contains_cone(outer: (glam::Vec3, f32), inner: (glam::Vec3, f32)) -> bool {
+        let theta_outer = outer.1.clamp(-1.0, 1.0).acos();
+        let theta_inner = inner.1.clamp(-1.0, 1.0).acos();
+        outer.1 <= -1.0 || outer.0.angle_between(inner.0) + theta_inner <= theta_outer + 1e-4
+    }
+
+    fn bounds(position: glam::Vec3, direction: glam::Vec3, cos_theta_o: f32) -> LightBounds {
+        LightBounds {
+            min: position - 0.1,
+            max: position + 0.1,
+            power: 1.0 + position.x.abs(),
+            direction,
+            cos_theta_o,
+            cos_theta_e: cos_theta_o.max(0.0),
+            two_sided: position.y > 0.0,
+        }
+    }
+
+    #[test]
+    fn cone_union_contains_both_cones() {
+        let cones = [
+            (glam::Vec3::Z, 1.0),
+            (glam::Vec3::Z, 0.9),
+            (glam::Vec3::X, 0.5),
+            (glam::Vec3::NEG_Z, 1.0),
+            (glam::vec3(1.0, 1.0, 0.0).normalize(), 0.99),
+            (glam::Vec3::Y, -1.0),
+        ];
+        for a in cones {
+            for b in cones {
+                let union = cone_union(a, b);
+                assert!(
+                    contains_cone(union, a),
+                    "{:?} doesn't contain {:?}",
+                    union,
+                    a
+                );
+                assert!(
+                    contains_cone(union, b),
+                    "{:?} doesn't contain {:?}",
+                    union,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_light_bvh() {
+        let nodes = build_light_bvh(&[]);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].power, 0.0);
+    }
+
+    #[test]
+    fn light_bvh_layout() {
+        let directions = [
+            glam::Vec3::Y,
+            glam::Vec3::NEG_Y,
+            glam::Vec3::X,
+            glam::Vec3::Z,
+        ];
+        let lights: Vec<(u32, LightBounds)> = (0..23)
+            .map(|i| {
+                let position = glam::vec3((i % 5) as f32, (i % 3) as f32 - 1.0, (i / 5) as f32);
+                let cos_theta_o = if i % 4 == 3 { -1.0 } else { 0.8 };
+                (
+                    10 + i,
+                    bounds(position, directions[i as usize % 4], cos_theta_o),
+                )
+            })
+            .collect();
+        let nodes = build_light_bvh(&lights);
+        assert_eq!(nodes.len(), 2 * lights.len() - 1);
+
+        // walks the subtree, checks that parents contain their children and returns the lights
+        // of its leaves and the index following the subtree
+        fn walk(nodes: &[GpuLightBvhNode], index: usize, leaves: &mut Vec<u32>) -> usize {
+            let node = &nodes[index];
+            if node.flags & GpuLightBvhNode::FLAG_LEAF != 0 {
+                leaves.push(node.index);
+                return index + 1;
+            }
+
+            // the first child follows its parent, the second one ends the first subtree
+            let children = [index + 1, node.index as usize];
+            assert_eq!(walk(nodes, children[0], leaves), children[1]);
+            let end = walk(nodes, children[1], leaves);
+
+            let power: f32 = children.iter().map(|&child| nodes[child].power).sum();
+            assert!((node.power - power).abs() < 1e-4);
+            for child in children.map(|child| &nodes[child]) {
+                assert!(node.bounds_min.cmple(child.bounds_min).all());
+                assert!(node.bounds_max.cmpge(child.bounds_max).all());
+                assert!(contains_cone(
+                    (node.direction, node.cos_theta_o),
+                    (child.direction, child.cos_theta_o)
+                ));
+                assert!(node.cos_theta_e <= child.cos_theta_e);
+                if child.flags & GpuLightBvhNode::FLAG_TWO_SIDED != 0 {
+                    assert!(node.flags & GpuLightBvhNode::FLAG_TWO_SIDED != 0);
+                }
+            }
+            end
+        }
+
+        let mut leaves = Vec::new();
+        assert_eq!(walk(&nodes, 0, &mut leaves), nodes.len());
+        leaves.sort();
+        let light_indices: Vec<u32> = lights.iter().map(|(index, _)| *index).collect();
+        assert_eq!(leaves, light_indices);
+
+        for (index, light) in lights.iter() {
+            let leaf = nodes
+                .iter()
+                .find(|node| node.flags & GpuLightBvhNode::FLAG_LEAF != 0 && node.index == *index);
+            let leaf = leaf.unwrap();
+            assert_eq!(leaf.bounds_min, light.min);
+            assert_eq!(leaf.power, light.power);
+        }
+    }
+}
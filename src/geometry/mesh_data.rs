@@ -0,0 +1,152 @@
+use crate::geometry::{Triangle, Vertex};
+
+// vertex attributes of a part of a mesh as read from a file. attributes that are missing or have
+// the wrong length are replaced by defaults, without normals the part is flat shaded and without
+// tangents they are generated from the first texture coordinate set
+pub(super) struct MeshData<'a> {
+    pub positions: &'a [glam::Vec3],
+    pub normals: &'a [glam::Vec3],
+    pub tex_coords: &'a [glam::Vec2],
+    pub tex_coords_1: &'a [glam::Vec2],
+    pub tangents: &'a [glam::Vec4],
+    pub colors: &'a [glam::Vec4],
+    pub indices: &'a [u32],
+    pub material_index: u32,
+}
+
+// triangles referencing vertices that don't exist are dropped
+pub(super) fn append_mesh_data(
+    data: MeshData,
+    vertices: &mut Vec<Vertex>,
+    triangles: &mut Vec<Triangle>,
+) {
+    let tex_coord = |i: usize| data.tex_coords.get(i).copied().unwrap_or(glam::Vec2::ZERO);
+    let tex_coord_1 = |i: usize| {
+        data.tex_coords_1
+            .get(i)
+            .copied()
+            .unwrap_or(glam::Vec2::ZERO)
+    };
+    let color = |i: usize| data.colors.get(i).copied().unwrap_or(glam::Vec4::ONE);
+    let has_tangents = data.tangents.len() == data.positions.len();
+    let tangent = |i: usize| {
+        if has_tangents {
+            data.tangents[i]
+        } else {
+            glam::Vec4::ZERO
+        }
+    };
+    let first_vertex = vertices.len();
+    let first_triangle = triangles.len();
+
+    let valid_triangles = data.indices.chunks_exact(3).filter(|triangle| {
+        triangle
+            .iter()
+            .all(|&i| (i as usize) < data.positions.len())
+    });
+
+    if data.normals.len() == data.positions.len() {
+        let index_offset = vertices.len() as u32;
+        for (i, (position, normal)) in data.positions.iter().zip(data.normals).enumerate() {
+            vertices.push(Vertex {
+                position: *position,
+                tex_coord: tex_coord(i),
+                tex_coord_1: tex_coord_1(i),
+                normal: *normal,
+                tangent: tangent(i),
+                color: color(i),
+            });
+        }
+
+        for triangle in valid_triangles {
+            triangles.push(Triangle {
+                vertex_indices: [
+                    index_offset + triangle[0],
+                    index_offset + triangle[1],
+                    index_offset + triangle[2],
+                ],
+                material_index: data.material_index,
+            });
+        }
+    } else {
+        // flat shaded vertices can't be shared between triangles
+        for triangle in valid_triangles {
+            let [p0, p1, p2] = [0, 1, 2].map(|k| data.positions[triangle[k] as usize]);
+            let normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+
+            let index_offset = vertices.len() as u32;
+            for &i in triangle {
+                vertices.push(Vertex {
+                    position: data.positions[i as usize],
+                    tex_coord: tex_coord(i as usize),
+                    tex_coord_1: tex_coord_1(i as usize),
+                    normal,
+                    tangent: tangent(i as usize),
+                    color: color(i as usize),
+                });
+            }
+
+            triangles.push(Triangle {
+                vertex_indices: [index_offset, index_offset + 1, index_offset + 2],
+                material_index: data.material_index,
+            });
+        }
+    }
+    if !has_tangents && !data.tex_coords.is_empty() {
+        let mut tangent_space = TangentSpace {
+            vertices: &mut vertices[first_vertex..],
+            triangles: &triangles[first_triangle..],
+            first_vertex: first_vertex as u32,
+        };
+        if !bevy_mikktspace::generate_tangents(&mut tangent_space) {
+            log::warn!("failed to generate tangents");
+        }
+    }
+}
+
+// the appended part of a mesh as seen by the mikktspace tangent generation
+struct TangentSpace<'a> {
+    vertices: &'a mut [Vertex],
+    triangles: &'a [Triangle],
+    first_vertex: u32,
+}
+
+impl TangentSpace<'_> {
+    fn vertex_index(&self, face: usize, vert: usize) -> usize {
+        (self.triangles[face].vertex_indices[vert] - self.first_vertex) as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentSpace<'_> {
+    fn num_faces(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.vertex_index(face, vert)]
+            .position
+            .to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.vertex_index(face, vert)]
+            .normal
+            .to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[self.vertex_index(face, vert)]
+            .tex_coord
+            .to_array()
+    }
+
+    // shared vertices keep the tangent of the last face, seams of the tangent space aren't split
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.vertex_index(face, vert);
+        self.vertices[index].tangent = glam::Vec4::from(tangent);
+    }
+}
@@ -1,40 +1,81 @@
+pub use bvh::GpuBvhNode;
 pub use for_gpu::*;
+#[cfg(feature = "gltf")]
+pub use gltf_loader::{GltfCamera, GltfError, GltfNodeInfo, GltfSceneInfo, GltfSelection};
+pub use light::*;
+pub use light_bvh::GpuLightBvhNode;
+#[cfg(feature = "obj")]
+pub use obj_loader::ObjError;
+pub use ply_loader::PlyError;
+pub use primitive::*;
+pub use stl_loader::StlError;
+
+use crate::{ies::IesProfile, ValidationError};
 
+mod bvh;
 mod for_gpu;
 #[cfg(feature = "gltf")]
 mod gltf_loader;
+mod light;
+mod light_bvh;
+mod mesh_data;
+#[cfg(feature = "obj")]
+mod obj_loader;
+mod ply_loader;
+mod primitive;
+mod stl_loader;
 
 #[derive(Clone, Debug)]
 pub struct Geometry {
+    pub textures: Vec<Texture>,
     pub materials: Vec<Material>,
     pub vertices: Vec<Vertex>,
     pub triangles: Vec<Triangle>,
+    // triangles are only rendered through the instances of the meshes containing them
+    pub meshes: Vec<Mesh>,
+    pub instances: Vec<Instance>,
+    pub primitives: Vec<Primitive>,
+    pub lights: Vec<Light>,
+    pub ies_profiles: Vec<IesProfile>,
 }
 
 impl Default for Geometry {
     fn default() -> Self {
         Self {
+            textures: Vec::new(),
             materials: vec![Material::default()],
             vertices: vec![
                 Vertex {
                     position: glam::vec3(-1.0, -1.0, 0.0),
                     tex_coord: glam::vec2(0.0, 0.0),
+                    tex_coord_1: glam::Vec2::ZERO,
                     normal: glam::Vec3::Z,
+                    tangent: glam::vec4(1.0, 0.0, 0.0, 1.0),
+                    color: glam::Vec4::ONE,
                 },
                 Vertex {
                     position: glam::vec3(1.0, -1.0, 0.0),
                     tex_coord: glam::vec2(1.0, 0.0),
+                    tex_coord_1: glam::Vec2::ZERO,
                     normal: glam::Vec3::Z,
+                    tangent: glam::vec4(1.0, 0.0, 0.0, 1.0),
+                    color: glam::Vec4::ONE,
                 },
                 Vertex {
                     position: glam::vec3(1.0, 1.0, 0.0),
                     tex_coord: glam::vec2(1.0, 1.0),
+                    tex_coord_1: glam::Vec2::ZERO,
                     normal: glam::Vec3::Z,
+                    tangent: glam::vec4(1.0, 0.0, 0.0, 1.0),
+                    color: glam::Vec4::ONE,
                 },
                 Vertex {
                     position: glam::vec3(-1.0, 1.0, 0.0),
                     tex_coord: glam::vec2(0.0, 1.0),
+                    tex_coord_1: glam::Vec2::ZERO,
                     normal: glam::Vec3::Z,
+                    tangent: glam::vec4(1.0, 0.0, 0.0, 1.0),
+                    color: glam::Vec4::ONE,
                 },
             ],
             triangles: vec![
@@ -47,28 +88,203 @@ impl Default for Geometry {
                     material_index: 0,
                 },
             ],
+            meshes: vec![Mesh {
+                first_triangle: 0,
+                num_triangles: 2,
+            }],
+            instances: vec![Instance::default()],
+            primitives: Vec::new(),
+            lights: Vec::new(),
+            ies_profiles: Vec::new(),
         }
     }
 }
 
 impl Geometry {
     #[cfg(feature = "gltf")]
-    pub fn load(path: &str, scene_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        gltf_loader::load(path, scene_name)
+    pub fn load(path: &str, selection: GltfSelection) -> Result<Self, GltfError> {
+        gltf_loader::load(path, selection).map(|(geometry, _)| geometry)
+    }
+
+    // also returns the cameras of the selection with their world transforms
+    #[cfg(feature = "gltf")]
+    pub fn load_with_cameras(
+        path: &str,
+        selection: GltfSelection,
+    ) -> Result<(Self, Vec<GltfCamera>), GltfError> {
+        gltf_loader::load(path, selection)
+    }
+
+    #[cfg(feature = "gltf")]
+    pub fn list_gltf_scenes(path: &str) -> Result<Vec<GltfSceneInfo>, GltfError> {
+        gltf_loader::list_scenes(path)
+    }
+
+    // every group or object of the file becomes a mesh with one instance
+    #[cfg(feature = "obj")]
+    pub fn load_obj(path: &str) -> Result<Self, ObjError> {
+        obj_loader::load(path)
+    }
+
+    // ascii or binary, vertex colors are kept as vertex colors of a single white material
+    pub fn load_ply(path: &str) -> Result<Self, PlyError> {
+        ply_loader::load(path)
     }
 
-    pub fn validate(&self) -> bool {
-        for triangle in self.triangles.iter() {
-            if triangle
+    // ascii or binary, the facet normals of the file are replaced by flat normals
+    pub fn load_stl(path: &str) -> Result<Self, StlError> {
+        stl_loader::load(path)
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.materials.is_empty() {
+            return Err(ValidationError::NoMaterials);
+        }
+        // geometry made only of primitives needs neither vertices nor triangles
+        if self.primitives.is_empty() {
+            if self.vertices.is_empty() {
+                return Err(ValidationError::NoVertices);
+            }
+            if self.triangles.is_empty() {
+                return Err(ValidationError::NoTriangles);
+            }
+        }
+        for (index, texture) in self.textures.iter().enumerate() {
+            texture
+                .validate()
+                .map_err(|reason| ValidationError::Texture { index, reason })?;
+        }
+        if self.ies_profiles.len() > MAX_IES_PROFILES {
+            return Err(ValidationError::TooManyIesProfiles {
+                len: self.ies_profiles.len(),
+                max: MAX_IES_PROFILES,
+            });
+        }
+        for (index, profile) in self.ies_profiles.iter().enumerate() {
+            profile
+                .validate()
+                .map_err(|reason| ValidationError::IesProfile { index, reason })?;
+        }
+        for (index, light) in self.lights.iter().enumerate() {
+            validate_light(index, light, self.ies_profiles.len())?;
+        }
+        for (index, material) in self.materials.iter().enumerate() {
+            validate_material(index, material, self.textures.len())?;
+        }
+        if let Some(vertex) = self.vertices.iter().position(|vertex| {
+            !vertex.position.is_finite()
+                || !vertex.normal.is_finite()
+                || !vertex.tex_coord.is_finite()
+                || !vertex.tex_coord_1.is_finite()
+                || !vertex.tangent.is_finite()
+                || !vertex.color.is_finite()
+        }) {
+            return Err(ValidationError::VertexNotFinite { vertex });
+        }
+        for (index, mesh) in self.meshes.iter().enumerate() {
+            if mesh.first_triangle as usize + mesh.num_triangles as usize > self.triangles.len() {
+                return Err(ValidationError::MeshTrianglesOutOfRange { mesh: index });
+            }
+        }
+        for (index, instance) in self.instances.iter().enumerate() {
+            if instance.mesh as usize >= self.meshes.len() {
+                return Err(ValidationError::InstanceMeshOutOfRange {
+                    instance: index,
+                    mesh: instance.mesh,
+                });
+            }
+            if let Some(material) = instance
+                .material
+                .filter(|material| *material as usize >= self.materials.len())
+            {
+                return Err(ValidationError::InstanceMaterialOutOfRange {
+                    instance: index,
+                    material,
+                });
+            }
+            if !is_valid_transform(instance.transform) {
+                return Err(ValidationError::InstanceTransform { instance: index });
+            }
+        }
+        for (index, primitive) in self.primitives.iter().enumerate() {
+            primitive
+                .validate()
+                .map_err(|reason| ValidationError::Primitive { index, reason })?;
+            if primitive.material_index as usize >= self.materials.len() {
+                return Err(ValidationError::PrimitiveMaterialOutOfRange {
+                    primitive: index,
+                    material: primitive.material_index,
+                });
+            }
+        }
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            if let Some(vertex) = triangle
                 .vertex_indices
-                .iter()
-                .any(|index| *index as usize >= self.vertices.len())
-                || triangle.material_index as usize >= self.materials.len()
+                .into_iter()
+                .find(|vertex| *vertex as usize >= self.vertices.len())
             {
-                return false;
+                return Err(ValidationError::VertexOutOfRange {
+                    triangle: index,
+                    vertex,
+                });
+            }
+            if triangle.material_index as usize >= self.materials.len() {
+                return Err(ValidationError::MaterialOutOfRange {
+                    triangle: index,
+                    material: triangle.material_index,
+                });
             }
         }
-        true
+        Ok(())
+    }
+
+    // world space area of all the instanced triangles per material
+    pub fn material_areas(&self) -> Vec<f32> {
+        let mut areas = vec![0.0; self.materials.len()];
+        for (material_index, [p0, p1, p2]) in self.world_triangles() {
+            areas[material_index as usize] += 0.5 * (p1 - p0).cross(p2 - p0).length();
+        }
+        areas
+    }
+
+    pub fn find_instance(&self, name: &str) -> Option<usize> {
+        self.instances
+            .iter()
+            .position(|instance| instance.name.as_deref() == Some(name))
+    }
+
+    // whether any triangle of the instance is turned into a light
+    pub fn is_instance_emissive(&self, index: usize) -> bool {
+        let instance = &self.instances[index];
+        let mesh = &self.meshes[instance.mesh as usize];
+        let first = mesh.first_triangle as usize;
+        self.triangles[first..first + mesh.num_triangles as usize]
+            .iter()
+            .any(|triangle| {
+                let material = instance.material.unwrap_or(triangle.material_index);
+                self.materials[material as usize].is_emissive()
+            })
+    }
+
+    // material and world space positions of every triangle of every instance
+    pub fn world_triangles(&self) -> impl Iterator<Item = (u32, [glam::Vec3; 3])> + '_ {
+        self.instances.iter().flat_map(move |instance| {
+            let mesh = &self.meshes[instance.mesh as usize];
+            let first = mesh.first_triangle as usize;
+            self.triangles[first..first + mesh.num_triangles as usize]
+                .iter()
+                .map(move |triangle| {
+                    let positions = triangle.vertex_indices.map(|i| {
+                        instance
+                            .transform
+                            .transform_point3(self.vertices[i as usize].position)
+                    });
+                    (
+                        instance.material.unwrap_or(triangle.material_index),
+                        positions,
+                    )
+                })
+        })
     }
 }
 
@@ -78,6 +294,31 @@ pub struct Material {
     pub roughness: f32,
     pub metallic: f32,
     pub emission: glam::Vec3,
+    pub emission_strength: f32,
+    pub emission_unit: EmissionUnit,
+    pub ior: f32,
+    pub transmission: f32,
+    pub thin_walled: bool,
+    pub attenuation_color: glam::Vec3,
+    pub attenuation_distance: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub sheen_color: glam::Vec3,
+    pub sheen_roughness: f32,
+    pub specular: f32,
+    pub specular_color: glam::Vec3,
+    pub alpha: f32,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub base_color_texture: Option<u32>,
+    // texture coordinate set (0 or 1) the base color texture is sampled with
+    pub base_color_tex_coord: u32,
+    // linear tangent space normal map, the scale is applied to its x and y
+    pub normal_texture: Option<u32>,
+    pub normal_tex_coord: u32,
+    pub normal_scale: f32,
+    // single-sided surfaces can't be seen or hit from behind unless they are transmissive
+    pub double_sided: bool,
 }
 
 impl Default for Material {
@@ -87,15 +328,119 @@ impl Default for Material {
             roughness: 1.0,
             metallic: 0.0,
             emission: glam::Vec3::ZERO,
+            emission_strength: 1.0,
+            emission_unit: EmissionUnit::Radiance,
+            ior: 1.5,
+            transmission: 0.0,
+            thin_walled: false,
+            attenuation_color: glam::Vec3::ONE,
+            attenuation_distance: f32::INFINITY,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            sheen_color: glam::Vec3::ZERO,
+            sheen_roughness: 0.0,
+            specular: 1.0,
+            specular_color: glam::Vec3::ONE,
+            alpha: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            base_color_texture: None,
+            base_color_tex_coord: 0,
+            normal_texture: None,
+            normal_tex_coord: 0,
+            normal_scale: 1.0,
+            double_sided: true,
+        }
+    }
+}
+
+impl Material {
+    pub fn is_emissive(&self) -> bool {
+        self.emission.max_element() > 0.0 && self.emission_strength > 0.0
+    }
+
+    // converts the emission to the radiance used by the renderer (1.0 equals 1 nit), `area` is
+    // the total surface area of the triangles using this material
+    pub fn emitted_radiance(&self, area: f32) -> glam::Vec3 {
+        const LUMENS_PER_WATT: f32 = 683.0;
+
+        let luminance = self.emission.dot(glam::vec3(0.2126, 0.7152, 0.0722));
+        let color = if luminance > 0.0 {
+            self.emission / luminance
+        } else {
+            glam::Vec3::ZERO
+        };
+
+        // a lambertian emitter with radiance L emits a flux of L * pi * area
+        let flux_to_radiance = if area > 0.0 {
+            1.0 / (std::f32::consts::PI * area)
+        } else {
+            0.0
+        };
+
+        match self.emission_unit {
+            EmissionUnit::Radiance => self.emission * self.emission_strength,
+            EmissionUnit::Nits => color * self.emission_strength,
+            EmissionUnit::Lumens => color * self.emission_strength * flux_to_radiance,
+            EmissionUnit::Watts => {
+                color * self.emission_strength * LUMENS_PER_WATT * flux_to_radiance
+            }
         }
     }
 }
 
+// units of `Material::emission_strength`, photometric units only use the hue of `emission`
+// while flux based units are spread over all the triangles using the material
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmissionUnit {
+    #[default]
+    Radiance,
+    Nits,
+    Lumens,
+    Watts,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    Mask,
+    Blend,
+}
+
+// rgba8 texels, the color channels are srgb encoded when `srgb` is set
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub size: glam::UVec2,
+    pub data: Vec<u8>,
+    pub srgb: bool,
+}
+
+impl Texture {
+    // returns the reason the texture is invalid
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.size.x == 0 || self.size.y == 0 {
+            return Err("size must not be zero");
+        }
+        if self.size.x as usize * self.size.y as usize * 4 != self.data.len() {
+            return Err("data doesn't match the size");
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Vertex {
     pub position: glam::Vec3,
     pub tex_coord: glam::Vec2,
+    // second texture coordinate set, e.g. for lightmaps, selected per texture by the material
+    pub tex_coord_1: glam::Vec2,
     pub normal: glam::Vec3,
+    // xyz points along increasing u of the first texture coordinate set, w is the sign of the
+    // bitangent cross(normal, tangent), zero when the mesh has no texture coordinates
+    pub tangent: glam::Vec4,
+    // linear rgba multiplied into the albedo and alpha of the material
+    pub color: glam::Vec4,
 }
 
 #[derive(Clone, Debug)]
@@ -103,3 +448,88 @@ pub struct Triangle {
     pub vertex_indices: [u32; 3],
     pub material_index: u32,
 }
+
+// a range of triangles in object space
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub first_triangle: u32,
+    pub num_triangles: u32,
+}
+
+// places a mesh in the world, `material` replaces the materials of all its triangles
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub mesh: u32,
+    pub transform: glam::Mat4,
+    pub material: Option<u32>,
+    // the node name for instances loaded from gltf
+    pub name: Option<String>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            mesh: 0,
+            transform: glam::Mat4::IDENTITY,
+            material: None,
+            name: None,
+        }
+    }
+}
+
+pub(crate) fn validate_light(
+    index: usize,
+    light: &Light,
+    num_ies_profiles: usize,
+) -> Result<(), ValidationError> {
+    light
+        .validate()
+        .map_err(|reason| ValidationError::Light { index, reason })?;
+    if let Some(ies_profile) = light
+        .ies_profile
+        .filter(|ies_profile| *ies_profile as usize >= num_ies_profiles)
+    {
+        return Err(ValidationError::LightIesProfileOutOfRange {
+            light: index,
+            ies_profile,
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_material(
+    index: usize,
+    material: &Material,
+    num_textures: usize,
+) -> Result<(), ValidationError> {
+    let textures = [
+        (material.base_color_texture, material.base_color_tex_coord),
+        (material.normal_texture, material.normal_tex_coord),
+    ];
+    for (texture, tex_coord) in textures {
+        if let Some(texture) = texture.filter(|texture| *texture as usize >= num_textures) {
+            return Err(ValidationError::MaterialTextureOutOfRange {
+                material: index,
+                texture,
+            });
+        }
+        if tex_coord > 1 {
+            return Err(ValidationError::MaterialTexCoordOutOfRange {
+                material: index,
+                tex_coord,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_valid_transform(transform: glam::Mat4) -> bool {
+    transform.is_finite() && transform.determinant() != 0.0
+}
+
+impl Triangle {
+    pub fn area(&self, vertices: &[Vertex]) -> f32 {
+        let [p0, p1, p2] = self.vertex_indices.map(|i| vertices[i as usize].position);
+        0.5 * (p1 - p0).cross(p2 - p0).length()
+    }
+}
@@ -0,0 +1,147 @@
+use crate::geometry::{
+    mesh_data::{append_mesh_data, MeshData},
+    AlphaMode, Geometry, Instance, Material, Mesh,
+};
+
+pub fn load(path: &str) -> Result<Geometry, ObjError> {
+    let (models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    // a missing or broken mtl file leaves every face with the default material
+    let obj_materials = obj_materials.unwrap_or_else(|err| {
+        log::warn!("failed to load the materials of {}: {}", path, err);
+        Vec::new()
+    });
+
+    let mut geometry = Geometry {
+        textures: Vec::new(),
+        materials: vec![Material::default()],
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+        meshes: Vec::new(),
+        instances: Vec::new(),
+        primitives: Vec::new(),
+        lights: Vec::new(),
+        ies_profiles: Vec::new(),
+    };
+    geometry
+        .materials
+        .extend(obj_materials.iter().map(convert_material));
+
+    for model in models {
+        let obj_mesh = &model.mesh;
+        let material_index = obj_mesh
+            .material_id
+            .filter(|id| *id < obj_materials.len())
+            .map_or(0, |id| id as u32 + 1);
+
+        let positions: Vec<glam::Vec3> = obj_mesh
+            .positions
+            .chunks_exact(3)
+            .map(glam::Vec3::from_slice)
+            .collect();
+        let normals: Vec<glam::Vec3> = obj_mesh
+            .normals
+            .chunks_exact(3)
+            .map(|normal| glam::Vec3::from_slice(normal).normalize_or_zero())
+            .collect();
+        // obj puts the origin of texture coordinates at the bottom left
+        let tex_coords: Vec<glam::Vec2> = obj_mesh
+            .texcoords
+            .chunks_exact(2)
+            .map(|tex_coord| glam::vec2(tex_coord[0], 1.0 - tex_coord[1]))
+            .collect();
+
+        let first_triangle = geometry.triangles.len() as u32;
+        append_mesh_data(
+            MeshData {
+                positions: &positions,
+                normals: &normals,
+                tex_coords: &tex_coords,
+                tex_coords_1: &[],
+                tangents: &[],
+                colors: &[],
+                indices: &obj_mesh.indices,
+                material_index,
+            },
+            &mut geometry.vertices,
+            &mut geometry.triangles,
+        );
+
+        geometry.meshes.push(Mesh {
+            first_triangle,
+            num_triangles: geometry.triangles.len() as u32 - first_triangle,
+        });
+        geometry.instances.push(Instance {
+            mesh: geometry.meshes.len() as u32 - 1,
+            name: Some(model.name),
+            ..Default::default()
+        });
+    }
+
+    Ok(geometry)
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Load(tobj::LoadError),
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Load(err) => write!(f, "failed to load obj: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<tobj::LoadError> for ObjError {
+    fn from(err: tobj::LoadError) -> Self {
+        ObjError::Load(err)
+    }
+}
+
+fn convert_material(obj_material: &tobj::Material) -> Material {
+    // Pr and Pm come from the pbr extension of mtl, without Pr the roughness is derived from the
+    // blinn-phong exponent through the beckmann distribution alpha = sqrt(2 / (Ns + 2))
+    let roughness = param_f32(obj_material, "Pr").unwrap_or_else(|| {
+        let shininess = obj_material.shininess.unwrap_or(0.0).max(0.0);
+        (2.0 / (shininess + 2.0)).sqrt().sqrt()
+    });
+    let alpha = obj_material.dissolve.unwrap_or(1.0).clamp(0.0, 1.0);
+
+    Material {
+        albedo: obj_material
+            .diffuse
+            .map_or(glam::Vec3::splat(0.8), glam::Vec3::from),
+        roughness: roughness.clamp(0.0, 1.0),
+        metallic: param_f32(obj_material, "Pm").unwrap_or(0.0).clamp(0.0, 1.0),
+        emission: param_vec3(obj_material, "Ke").unwrap_or(glam::Vec3::ZERO),
+        alpha,
+        alpha_mode: if alpha < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        },
+        ..Default::default()
+    }
+}
+
+fn param_f32(obj_material: &tobj::Material, name: &str) -> Option<f32> {
+    obj_material.unknown_param.get(name)?.trim().parse().ok()
+}
+
+fn param_vec3(obj_material: &tobj::Material, name: &str) -> Option<glam::Vec3> {
+    let values: Vec<f32> = obj_material
+        .unknown_param
+        .get(name)?
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match values[..] {
+        [value] => Some(glam::Vec3::splat(value)),
+        [x, y, z] => Some(glam::vec3(x, y, z)),
+        _ => None,
+    }
+}
@@ -0,0 +1,528 @@
+use crate::geometry::{
+    mesh_data::{append_mesh_data, MeshData},
+    Geometry, Instance, Material, Mesh,
+};
+
+// reads ascii and binary ply files with an optional face element. per vertex colors become vertex
+// colors of a single white material, polygons are triangulated as fans
+pub fn load(path: &str) -> Result<Geometry, PlyError> {
+    parse(&std::fs::read(path)?)
+}
+
+fn parse(data: &[u8]) -> Result<Geometry, PlyError> {
+    let (header, body) = parse_header(data)?;
+
+    let mut reader = match header.format {
+        Format::Ascii => BodyReader::Ascii(
+            std::str::from_utf8(body)
+                .map_err(|_| PlyError::InvalidData("ascii body isn't valid utf-8"))?
+                .split_ascii_whitespace(),
+        ),
+        Format::BinaryLittleEndian => BodyReader::Binary {
+            data: body,
+            big_endian: false,
+        },
+        Format::BinaryBigEndian => BodyReader::Binary {
+            data: body,
+            big_endian: true,
+        },
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+
+    for element in header.elements.iter() {
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |names: &[&str]| {
+                    element.properties.iter().position(|property| {
+                        property.list.is_none() && names.contains(&property.name.as_str())
+                    })
+                };
+                let find_all = |names: &[&[&str]]| -> Option<Vec<usize>> {
+                    names.iter().map(|names| find(names)).collect()
+                };
+
+                let position = find_all(&[&["x"], &["y"], &["z"]])
+                    .ok_or(PlyError::InvalidHeader("vertices have no position"))?;
+                let normal = find_all(&[&["nx"], &["ny"], &["nz"]]);
+                let tex_coord = find_all(&[
+                    &["u", "s", "texture_u", "texture_s"],
+                    &["v", "t", "texture_v", "texture_t"],
+                ]);
+                let color = find_all(&[
+                    &["red", "r", "diffuse_red"],
+                    &["green", "g", "diffuse_green"],
+                    &["blue", "b", "diffuse_blue"],
+                ]);
+                let alpha = find(&["alpha", "a"]);
+
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    for (value, property) in values.iter_mut().zip(element.properties.iter()) {
+                        *value = reader.read_property(property)?;
+                    }
+
+                    let vec3 = |i: &[usize]| {
+                        glam::dvec3(values[i[0]], values[i[1]], values[i[2]]).as_vec3()
+                    };
+                    positions.push(vec3(&position));
+                    if let Some(normal) = &normal {
+                        normals.push(vec3(normal).normalize_or_zero());
+                    }
+                    // ply puts the origin of texture coordinates at the bottom left
+                    if let Some(tex_coord) = &tex_coord {
+                        tex_coords.push(glam::vec2(
+                            values[tex_coord[0]] as f32,
+                            1.0 - values[tex_coord[1]] as f32,
+                        ));
+                    }
+                    if let Some(color) = &color {
+                        let channel =
+                            |i: usize| values[i] as f32 / element.properties[i].ty.color_scale();
+                        // colors of scans are display referred, so they are decoded from srgb
+                        let rgb =
+                            glam::vec3(channel(color[0]), channel(color[1]), channel(color[2]))
+                                .clamp(glam::Vec3::ZERO, glam::Vec3::ONE)
+                                .to_array()
+                                .map(srgb_to_linear);
+                        let a = alpha.map_or(1.0, |i| channel(i).clamp(0.0, 1.0));
+                        colors.push(glam::Vec3::from(rgb).extend(a));
+                    }
+                }
+            }
+            "face" => {
+                let (vertex_indices, count_ty, item_ty) = element
+                    .properties
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, property)| match property.list {
+                        Some((count_ty, item_ty))
+                            if property.name == "vertex_indices"
+                                || property.name == "vertex_index" =>
+                        {
+                            Some((i, count_ty, item_ty))
+                        }
+                        _ => None,
+                    })
+                    .ok_or(PlyError::InvalidHeader("faces have no vertex indices"))?;
+
+                for _ in 0..element.count {
+                    for (i, property) in element.properties.iter().enumerate() {
+                        if i != vertex_indices {
+                            reader.read_property(property)?;
+                            continue;
+                        }
+
+                        let count = reader.read_count(count_ty)?;
+                        let polygon = (0..count)
+                            .map(|_| reader.read(item_ty).map(|index| index as u32))
+                            .collect::<Result<Vec<u32>, PlyError>>()?;
+                        for k in 2..polygon.len() {
+                            indices.extend([polygon[0], polygon[k - 1], polygon[k]]);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for property in element.properties.iter() {
+                        reader.read_property(property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let material = Material {
+        albedo: if colors.is_empty() {
+            glam::Vec3::splat(0.8)
+        } else {
+            glam::Vec3::ONE
+        },
+        ..Default::default()
+    };
+
+    let mut geometry = Geometry {
+        textures: Vec::new(),
+        materials: vec![material],
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+        meshes: Vec::new(),
+        instances: vec![Instance::default()],
+        primitives: Vec::new(),
+        lights: Vec::new(),
+        ies_profiles: Vec::new(),
+    };
+    append_mesh_data(
+        MeshData {
+            positions: &positions,
+            normals: &normals,
+            tex_coords: &tex_coords,
+            tex_coords_1: &[],
+            tangents: &[],
+            colors: &colors,
+            indices: &indices,
+            material_index: 0,
+        },
+        &mut geometry.vertices,
+        &mut geometry.triangles,
+    );
+    // point clouds and faces with out of range indices leave nothing to render
+    if geometry.triangles.is_empty() {
+        return Err(PlyError::InvalidData("file contains no triangles"));
+    }
+    geometry.meshes.push(Mesh {
+        first_triangle: 0,
+        num_triangles: geometry.triangles.len() as u32,
+    });
+
+    Ok(geometry)
+}
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(std::io::Error),
+    InvalidHeader(&'static str),
+    InvalidData(&'static str),
+}
+
+impl std::fmt::Display for PlyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlyError::Io(err) => write!(f, "failed to read ply: {}", err),
+            PlyError::InvalidHeader(reason) => write!(f, "invalid ply header: {}", reason),
+            PlyError::InvalidData(reason) => write!(f, "invalid ply data: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+impl From<std::io::Error> for PlyError {
+    fn from(err: std::io::Error) -> Self {
+        PlyError::Io(err)
+    }
+}
+
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, PlyError> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return Err(PlyError::InvalidHeader("unknown property type")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    // integer colors span the whole range of their type, float colors are already normalized
+    fn color_scale(self) -> f32 {
+        match self {
+            ScalarType::I8 => i8::MAX as f32,
+            ScalarType::U8 => u8::MAX as f32,
+            ScalarType::I16 => i16::MAX as f32,
+            ScalarType::U16 => u16::MAX as f32,
+            ScalarType::I32 => i32::MAX as f32,
+            ScalarType::U32 => u32::MAX as f32,
+            ScalarType::F32 | ScalarType::F64 => 1.0,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    ty: ScalarType,
+    // types of the count and of the items for list properties
+    list: Option<(ScalarType, ScalarType)>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+// returns the header and the body following it
+fn parse_header(data: &[u8]) -> Result<(Header, &[u8]), PlyError> {
+    const END_HEADER: &[u8] = b"end_header";
+
+    let end = data
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or(PlyError::InvalidHeader("missing end_header"))?;
+    // the body starts after the line break, which may be \r\n
+    let body_start = data[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(data.len(), |i| end + i + 1);
+    let text = std::str::from_utf8(&data[..end])
+        .map_err(|_| PlyError::InvalidHeader("header isn't valid utf-8"))?;
+
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(PlyError::InvalidHeader("missing ply magic number"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        match words[..] {
+            ["format", name, _] => {
+                format = Some(match name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(PlyError::InvalidHeader("unknown format")),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_owned(),
+                count: count
+                    .parse()
+                    .map_err(|_| PlyError::InvalidHeader("invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => {
+                let count_ty = ScalarType::parse(count_ty)?;
+                let item_ty = ScalarType::parse(item_ty)?;
+                elements
+                    .last_mut()
+                    .ok_or(PlyError::InvalidHeader("property outside of an element"))?
+                    .properties
+                    .push(Property {
+                        name: name.to_owned(),
+                        ty: item_ty,
+                        list: Some((count_ty, item_ty)),
+                    });
+            }
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or(PlyError::InvalidHeader("property outside of an element"))?
+                .properties
+                .push(Property {
+                    name: name.to_owned(),
+                    ty: ScalarType::parse(ty)?,
+                    list: None,
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(PlyError::InvalidHeader("unknown header line")),
+        }
+    }
+
+    let format = format.ok_or(PlyError::InvalidHeader("missing format"))?;
+    Ok((Header { format, elements }, &data[body_start..]))
+}
+
+enum BodyReader<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], big_endian: bool },
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, PlyError> {
+        match self {
+            BodyReader::Ascii(words) => words
+                .next()
+                .ok_or(PlyError::InvalidData("unexpected end of file"))?
+                .parse()
+                .map_err(|_| PlyError::InvalidData("invalid number")),
+            BodyReader::Binary { data, big_endian } => {
+                let size = ty.size();
+                if data.len() < size {
+                    return Err(PlyError::InvalidData("unexpected end of file"));
+                }
+                let (bytes, rest) = data.split_at(size);
+                *data = rest;
+
+                let mut le = [0u8; 8];
+                le[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    le[..size].reverse();
+                }
+                let [b0, b1, b2, b3, ..] = le;
+                Ok(match ty {
+                    ScalarType::I8 => b0 as i8 as f64,
+                    ScalarType::U8 => b0 as f64,
+                    ScalarType::I16 => i16::from_le_bytes([b0, b1]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([b0, b1]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(le),
+                })
+            }
+        }
+    }
+
+    fn read_count(&mut self, ty: ScalarType) -> Result<usize, PlyError> {
+        let count = self.read(ty)?;
+        if !(count >= 0.0 && count.fract() == 0.0) {
+            return Err(PlyError::InvalidData("invalid list count"));
+        }
+        Ok(count as usize)
+    }
+
+    // lists are skipped and read as zero
+    fn read_property(&mut self, property: &Property) -> Result<f64, PlyError> {
+        match property.list {
+            Some((count_ty, item_ty)) => {
+                let count = self.read_count(count_ty)?;
+                for _ in 0..count {
+                    self.read(item_ty)?;
+                }
+                Ok(0.0)
+            }
+            None => self.read(property.ty),
+        }
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn binary_quad(
+        format: &str,
+        to_bytes: fn(f32) -> [u8; 4],
+        index: fn(i32) -> [u8; 4],
+    ) -> Vec<u8> {
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        for position in QUAD {
+            data.extend(position.into_iter().flat_map(to_bytes));
+        }
+        data.push(4);
+        data.extend((0..4).flat_map(index));
+        data
+    }
+
+    fn assert_quad(geometry: &Geometry) {
+        assert_eq!(geometry.triangles.len(), 2);
+        assert_eq!(geometry.meshes[0].num_triangles, 2);
+        for vertex in geometry.vertices.iter() {
+            assert!(QUAD.contains(&vertex.position.to_array()));
+            assert_eq!(vertex.normal, glam::Vec3::Z);
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let data = b"ply\nformat ascii 1.0\ncomment quad\nelement vertex 4\nproperty float x\n\
+            property float y\nproperty float z\nproperty uchar red\nproperty uchar green\n\
+            property uchar blue\nelement face 1\nproperty list uchar int vertex_indices\n\
+            end_header\n0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 255 0 0\n0 1 0 255 0 0\n4 0 1 2 3\n";
+        let geometry = parse(data).unwrap();
+        assert_quad(&geometry);
+        assert_eq!(geometry.vertices[0].color, glam::vec4(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let data = binary_quad("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        assert_quad(&parse(&data).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let data = binary_quad("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes);
+        assert_quad(&parse(&data).unwrap());
+    }
+
+    #[test]
+    fn truncated_body() {
+        let data = binary_quad("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        assert!(matches!(
+            parse(&data[..data.len() - 2]),
+            Err(PlyError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn overflowing_list_count() {
+        let mut data = binary_quad("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        let count = data.len() - 17;
+        data[count] = 255;
+        assert!(matches!(parse(&data), Err(PlyError::InvalidData(_))));
+    }
+
+    #[test]
+    fn negative_list_count() {
+        let data = b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+            property float z\nelement face 1\nproperty list int int vertex_indices\nend_header\n\
+            0 0 0\n1 0 0\n1 1 0\n-3 0 1 2\n";
+        assert!(matches!(parse(data), Err(PlyError::InvalidData(_))));
+    }
+
+    #[test]
+    fn no_triangles() {
+        let points =
+            b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+            property float z\nend_header\n0 0 0\n1 0 0\n1 1 0\n";
+        assert!(matches!(parse(points), Err(PlyError::InvalidData(_))));
+
+        let no_faces = b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+            property float y\nproperty float z\nelement face 0\n\
+            property list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n1 1 0\n";
+        assert!(matches!(parse(no_faces), Err(PlyError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,159 @@
+use super::bvh::Aabb;
+
+// analytic shapes that are intersected exactly instead of being tessellated. they are shaded like
+// triangles with a vertex color of one but ignore alpha, normal maps and single-sidedness. their
+// emission isn't sampled by next event estimation and flux based emission units don't count
+// their area
+#[derive(Clone, Debug)]
+pub struct Primitive {
+    pub kind: PrimitiveKind,
+    pub material_index: u32,
+}
+
+// the normals of planes and disks point to their front side
+#[derive(Clone, Debug)]
+pub enum PrimitiveKind {
+    Sphere {
+        center: glam::Vec3,
+        radius: f32,
+    },
+    Plane {
+        point: glam::Vec3,
+        normal: glam::Vec3,
+    },
+    // the identity rotation gives an axis aligned box
+    Box {
+        center: glam::Vec3,
+        half_extents: glam::Vec3,
+        rotation: glam::Quat,
+    },
+    Disk {
+        center: glam::Vec3,
+        normal: glam::Vec3,
+        radius: f32,
+    },
+}
+
+// planes are bounded for the bvh, they are intersected as infinite planes but rays are culled
+// by the bounds further away than this
+const PLANE_EXTENT: f32 = 1.0e6;
+
+impl Primitive {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self.kind {
+            PrimitiveKind::Sphere { center, radius } => {
+                if !center.is_finite() {
+                    return Err("center must be finite");
+                }
+                if !(radius > 0.0 && radius.is_finite()) {
+                    return Err("radius must be positive");
+                }
+            }
+            PrimitiveKind::Plane { point, normal } => {
+                if !point.is_finite() {
+                    return Err("point must be finite");
+                }
+                if !normal.is_finite() || normal.length_squared() == 0.0 {
+                    return Err("normal must not be zero");
+                }
+            }
+            PrimitiveKind::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                if !center.is_finite() {
+                    return Err("center must be finite");
+                }
+                if !(half_extents.min_element() > 0.0 && half_extents.is_finite()) {
+                    return Err("half extents must be positive");
+                }
+                if !rotation.is_finite() || rotation.length_squared() == 0.0 {
+                    return Err("rotation must not be zero");
+                }
+            }
+            PrimitiveKind::Disk {
+                center,
+                normal,
+                radius,
+            } => {
+                if !center.is_finite() {
+                    return Err("center must be finite");
+                }
+                if !normal.is_finite() || normal.length_squared() == 0.0 {
+                    return Err("normal must not be zero");
+                }
+                if !(radius > 0.0 && radius.is_finite()) {
+                    return Err("radius must be positive");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // maps the canonical shape intersected by the shader to the primitive: the unit sphere, the
+    // z = 0 plane, the box from -1 to 1 and the unit disk in the z = 0 plane, facing +Z
+    pub(super) fn transform(&self) -> glam::Mat4 {
+        let facing =
+            |normal: glam::Vec3| glam::Quat::from_rotation_arc(glam::Vec3::Z, normal.normalize());
+
+        match self.kind {
+            PrimitiveKind::Sphere { center, radius } => {
+                glam::Mat4::from_scale_rotation_translation(
+                    glam::Vec3::splat(radius),
+                    glam::Quat::IDENTITY,
+                    center,
+                )
+            }
+            PrimitiveKind::Plane { point, normal } => {
+                glam::Mat4::from_rotation_translation(facing(normal), point)
+            }
+            PrimitiveKind::Box {
+                center,
+                half_extents,
+                rotation,
+            } => glam::Mat4::from_scale_rotation_translation(
+                half_extents,
+                rotation.normalize(),
+                center,
+            ),
+            PrimitiveKind::Disk {
+                center,
+                normal,
+                radius,
+            } => glam::Mat4::from_scale_rotation_translation(
+                glam::Vec3::splat(radius),
+                facing(normal),
+                center,
+            ),
+        }
+    }
+
+    // object space bounds of the canonical shape
+    pub(super) fn bounds(&self) -> Aabb {
+        match self.kind {
+            PrimitiveKind::Sphere { .. } | PrimitiveKind::Box { .. } => Aabb {
+                min: glam::Vec3::NEG_ONE,
+                max: glam::Vec3::ONE,
+            },
+            PrimitiveKind::Plane { .. } => Aabb {
+                min: glam::vec3(-PLANE_EXTENT, -PLANE_EXTENT, 0.0),
+                max: glam::vec3(PLANE_EXTENT, PLANE_EXTENT, 0.0),
+            },
+            PrimitiveKind::Disk { .. } => Aabb {
+                min: glam::vec3(-1.0, -1.0, 0.0),
+                max: glam::vec3(1.0, 1.0, 0.0),
+            },
+        }
+    }
+
+    // matches the instance kinds of the shader, 0 is a mesh
+    pub(super) fn gpu_kind(&self) -> u32 {
+        match self.kind {
+            PrimitiveKind::Sphere { .. } => 1,
+            PrimitiveKind::Plane { .. } => 2,
+            PrimitiveKind::Box { .. } => 3,
+            PrimitiveKind::Disk { .. } => 4,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::geometry::{
+    mesh_data::{append_mesh_data, MeshData},
+    Geometry, Instance, Material, Mesh,
+};
+
+// binary files are recognised by their size because ascii exporters aren't the only ones that
+// start the file with "solid"
+pub fn load(path: &str) -> Result<Geometry, StlError> {
+    parse(&std::fs::read(path)?)
+}
+
+fn parse(data: &[u8]) -> Result<Geometry, StlError> {
+    let is_binary = data.len() >= 84
+        && 84 + 50 * u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize
+            == data.len();
+    let positions = if is_binary {
+        parse_binary(data)
+    } else if data.starts_with(b"solid") {
+        parse_ascii(data)?
+    } else {
+        return Err(StlError::InvalidData("neither an ascii nor a binary stl"));
+    };
+
+    let mut geometry = Geometry {
+        textures: Vec::new(),
+        materials: vec![Material {
+            albedo: glam::Vec3::splat(0.8),
+            ..Default::default()
+        }],
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+        meshes: Vec::new(),
+        instances: vec![Instance::default()],
+        primitives: Vec::new(),
+        lights: Vec::new(),
+        ies_profiles: Vec::new(),
+    };
+    // the facet normals of stl files are often zero or wrong, so flat normals are generated
+    // from the winding instead
+    let indices: Vec<u32> = (0..positions.len() as u32).collect();
+    append_mesh_data(
+        MeshData {
+            positions: &positions,
+            normals: &[],
+            tex_coords: &[],
+            tex_coords_1: &[],
+            tangents: &[],
+            colors: &[],
+            indices: &indices,
+            material_index: 0,
+        },
+        &mut geometry.vertices,
+        &mut geometry.triangles,
+    );
+    if geometry.triangles.is_empty() {
+        return Err(StlError::InvalidData("file contains no triangles"));
+    }
+    geometry.meshes.push(Mesh {
+        first_triangle: 0,
+        num_triangles: geometry.triangles.len() as u32,
+    });
+
+    Ok(geometry)
+}
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(std::io::Error),
+    InvalidData(&'static str),
+}
+
+impl std::fmt::Display for StlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StlError::Io(err) => write!(f, "failed to read stl: {}", err),
+            StlError::InvalidData(reason) => write!(f, "invalid stl data: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for StlError {}
+
+impl From<std::io::Error> for StlError {
+    fn from(err: std::io::Error) -> Self {
+        StlError::Io(err)
+    }
+}
+
+// 80 byte header, triangle count, then per triangle a normal, three vertices and two attribute
+// bytes
+fn parse_binary(data: &[u8]) -> Vec<glam::Vec3> {
+    let read_vec3 = |bytes: &[u8]| {
+        let component = |i: usize| f32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+        glam::vec3(component(0), component(1), component(2))
+    };
+
+    data[84..]
+        .chunks_exact(50)
+        .flat_map(|facet| (1..4).map(move |i| read_vec3(&facet[12 * i..12 * (i + 1)])))
+        .collect()
+}
+
+fn parse_ascii(data: &[u8]) -> Result<Vec<glam::Vec3>, StlError> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| StlError::InvalidData("ascii stl isn't valid utf-8"))?;
+
+    let mut positions = Vec::new();
+    let mut tokens = text.split_ascii_whitespace();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue;
+        }
+        let mut component = || {
+            tokens
+                .next()
+                .and_then(|value| value.parse::<f32>().ok())
+                .ok_or(StlError::InvalidData("vertex with an invalid position"))
+        };
+        positions.push(glam::vec3(component()?, component()?, component()?));
+    }
+
+    if positions.len() % 3 != 0 {
+        return Err(StlError::InvalidData("facet without three vertices"));
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    fn binary(num_facets: u32) -> Vec<u8> {
+        let mut data = vec![0; 80];
+        data.extend(num_facets.to_le_bytes());
+        for _ in 0..num_facets {
+            data.extend([0u8; 12]);
+            data.extend(TRIANGLE.into_iter().flatten().flat_map(f32::to_le_bytes));
+            data.extend([0u8; 2]);
+        }
+        data
+    }
+
+    #[test]
+    fn ascii() {
+        let data = b"solid test\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n\
+            vertex 0 1 0\nendloop\nendfacet\nendsolid test\n";
+        let geometry = parse(data).unwrap();
+        assert_eq!(geometry.triangles.len(), 1);
+        for (vertex, position) in geometry.vertices.iter().zip(TRIANGLE) {
+            assert_eq!(vertex.position.to_array(), position);
+            assert_eq!(vertex.normal, glam::Vec3::Z);
+        }
+    }
+
+    #[test]
+    fn binary_starting_with_solid() {
+        let mut data = binary(2);
+        data[..5].copy_from_slice(b"solid");
+        let geometry = parse(&data).unwrap();
+        assert_eq!(geometry.triangles.len(), 2);
+        assert_eq!(geometry.vertices[2].position, glam::Vec3::Y);
+    }
+
+    #[test]
+    fn no_triangles() {
+        assert!(matches!(parse(&binary(0)), Err(StlError::InvalidData(_))));
+        assert!(matches!(
+            parse(b"solid empty\nendsolid empty\n"),
+            Err(StlError::InvalidData(_))
+        ));
+    }
+}
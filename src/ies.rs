@@ -0,0 +1,337 @@
+// IES LM-63 photometric profiles, only type C photometry is supported
+#[derive(Clone, Debug)]
+pub struct IesProfile {
+    // angles are in degrees, the vertical angle is measured from the nadir (the light axis)
+    pub vertical_angles: Vec<f32>,
+    pub horizontal_angles: Vec<f32>,
+    // candela values stored per horizontal angle: candela[h * vertical_angles.len() + v]
+    pub candela: Vec<f32>,
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> Result<Self, IesError> {
+        let bytes = std::fs::read(path)?;
+        Self::parse(&String::from_utf8_lossy(&bytes))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, IesError> {
+        let mut lines = text.lines();
+
+        // skip the version line and keywords until the tilt line
+        let tilt = loop {
+            let line = lines.next().ok_or(IesError::MissingTilt)?.trim();
+            if let Some(tilt) = line.strip_prefix("TILT=") {
+                break tilt.trim();
+            }
+        };
+
+        let mut values = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|value| !value.is_empty());
+        let mut next = || -> Result<f32, IesError> {
+            let value = values.next().ok_or(IesError::UnexpectedEnd)?;
+            value
+                .parse()
+                .map_err(|_| IesError::InvalidNumber(value.to_owned()))
+        };
+
+        // tilt data in a separate file isn't supported
+        match tilt {
+            "NONE" => {}
+            "INCLUDE" => {
+                let _lamp_to_luminaire_geometry = next()?;
+                let num_tilt_angles = next()? as usize;
+                for _ in 0..num_tilt_angles {
+                    let _angle = next()?;
+                    let _multiplying_factor = next()?;
+                }
+            }
+            _ => return Err(IesError::UnsupportedTilt(tilt.to_owned())),
+        }
+
+        let _num_lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let candela_multiplier = next()?;
+        let num_vertical_angles = next()? as usize;
+        let num_horizontal_angles = next()? as usize;
+        let photometric_type = next()? as u32;
+        let _units_type = next()?;
+        let _width = next()?;
+        let _length = next()?;
+        let _height = next()?;
+        let ballast_factor = next()?;
+        let _future_use = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != 1 {
+            return Err(IesError::UnsupportedPhotometricType(photometric_type));
+        }
+        let num_candela = num_vertical_angles
+            .checked_mul(num_horizontal_angles)
+            .ok_or(IesError::TooManyAngles)?;
+
+        let vertical_angles = (0..num_vertical_angles)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..num_horizontal_angles)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let candela = (0..num_candela)
+            .map(|_| next().map(|value| value * candela_multiplier * ballast_factor))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let profile = Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        };
+
+        profile.validate().map_err(|_| IesError::InvalidAngles)?;
+        Ok(profile)
+    }
+
+    // returns the reason the profile is invalid
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let sorted = |angles: &[f32]| angles.windows(2).all(|w| w[0] < w[1]);
+
+        if self.vertical_angles.is_empty() || self.horizontal_angles.is_empty() {
+            return Err("angles must not be empty");
+        }
+        if !sorted(&self.vertical_angles) || !sorted(&self.horizontal_angles) {
+            return Err("angles must be sorted");
+        }
+        if self.candela.len() != self.vertical_angles.len() * self.horizontal_angles.len() {
+            return Err("candela values don't match the angles");
+        }
+        Ok(())
+    }
+
+    pub fn max_candela(&self) -> f32 {
+        self.candela.iter().copied().fold(0.0, f32::max)
+    }
+
+    // interpolated candela value, angles are in degrees and the horizontal symmetry of the
+    // profile is applied
+    pub fn intensity(&self, vertical_angle: f32, horizontal_angle: f32) -> f32 {
+        let first_vertical = self.vertical_angles[0];
+        let last_vertical = *self.vertical_angles.last().unwrap();
+        if vertical_angle < first_vertical || vertical_angle > last_vertical {
+            return 0.0;
+        }
+
+        let mut horizontal_angle = horizontal_angle.rem_euclid(360.0);
+        let last_horizontal = *self.horizontal_angles.last().unwrap();
+        if last_horizontal <= 0.0 {
+            horizontal_angle = 0.0;
+        } else if last_horizontal <= 90.0 {
+            if horizontal_angle > 180.0 {
+                horizontal_angle = 360.0 - horizontal_angle;
+            }
+            if horizontal_angle > 90.0 {
+                horizontal_angle = 180.0 - horizontal_angle;
+            }
+        } else if last_horizontal <= 180.0 && horizontal_angle > 180.0 {
+            horizontal_angle = 360.0 - horizontal_angle;
+        }
+
+        let (v0, v1, tv) = interpolation(&self.vertical_angles, vertical_angle);
+        let (h0, h1, th) = interpolation(&self.horizontal_angles, horizontal_angle);
+
+        let num_vertical = self.vertical_angles.len();
+        let value = |h: usize, v: usize| self.candela[h * num_vertical + v];
+
+        let c0 = value(h0, v0) * (1.0 - tv) + value(h0, v1) * tv;
+        let c1 = value(h1, v0) * (1.0 - tv) + value(h1, v1) * tv;
+        c0 * (1.0 - th) + c1 * th
+    }
+
+    // resamples the profile normalized to its maximum, x maps to the horizontal angle [0, 360)
+    // and y to the vertical angle [0, 180]
+    pub(crate) fn to_table(&self, size: glam::UVec2) -> Vec<f32> {
+        let max_candela = self.max_candela();
+        let scale = if max_candela > 0.0 {
+            1.0 / max_candela
+        } else {
+            0.0
+        };
+
+        let mut table = Vec::with_capacity((size.x * size.y) as usize);
+        for y in 0..size.y {
+            let vertical_angle = 180.0 * y as f32 / (size.y - 1) as f32;
+            for x in 0..size.x {
+                let horizontal_angle = 360.0 * x as f32 / size.x as f32;
+                table.push(self.intensity(vertical_angle, horizontal_angle) * scale);
+            }
+        }
+        table
+    }
+}
+
+// returns the indices of the two angles around `angle` and the blend factor between them
+fn interpolation(angles: &[f32], angle: f32) -> (usize, usize, f32) {
+    let upper = angles.partition_point(|a| *a < angle);
+    if upper == 0 {
+        return (0, 0, 0.0);
+    }
+    if upper == angles.len() {
+        return (upper - 1, upper - 1, 0.0);
+    }
+
+    let lower = upper - 1;
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    (lower, upper, t)
+}
+
+#[derive(Debug)]
+pub enum IesError {
+    Io(std::io::Error),
+    MissingTilt,
+    UnexpectedEnd,
+    InvalidNumber(String),
+    InvalidAngles,
+    TooManyAngles,
+    UnsupportedTilt(String),
+    UnsupportedPhotometricType(u32),
+}
+
+impl std::fmt::Display for IesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IesError::Io(err) => write!(f, "failed to read ies file: {}", err),
+            IesError::MissingTilt => write!(f, "missing TILT line"),
+            IesError::UnexpectedEnd => write!(f, "unexpected end of file"),
+            IesError::InvalidNumber(value) => write!(f, "invalid number {:?}", value),
+            IesError::InvalidAngles => write!(f, "angles are not sorted or don't match the data"),
+            IesError::TooManyAngles => write!(f, "too many candela values for the angles"),
+            IesError::UnsupportedTilt(tilt) => write!(f, "unsupported tilt {:?}", tilt),
+            IesError::UnsupportedPhotometricType(ty) => {
+                write!(f, "unsupported photometric type {}", ty)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IesError {}
+
+impl From<std::io::Error> for IesError {
+    fn from(err: std::io::Error) -> Self {
+        IesError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(vertical_angles: &[f32], horizontal_angles: &[f32], candela: &[f32]) -> IesProfile {
+        let join = |values: &[f32]| {
+            let values: Vec<String> = values.iter().map(f32::to_string).collect();
+            values.join(" ")
+        };
+        let text = format!(
+            "IESNA:LM-63-2002\n[TEST] profile\nTILT=NONE\n1 1000 1 {} {} 1 1 0 0 0 1 1 100\n{}\n{}\n{}\n",
+            vertical_angles.len(),
+            horizontal_angles.len(),
+            join(vertical_angles),
+            join(horizontal_angles),
+            join(candela),
+        );
+        IesProfile::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn tilt_none() {
+        let text =
+            "IESNA:LM-63-2002\nTILT=NONE\n1 1000 2 2 1 1 1 0 0 0 0.5 1 100\n0 90\n0\n10, 5\n";
+        let profile = IesProfile::parse(text).unwrap();
+        assert_eq!(profile.vertical_angles, [0.0, 90.0]);
+        assert_eq!(profile.horizontal_angles, [0.0]);
+        // scaled by the candela multiplier and the ballast factor
+        assert_eq!(profile.candela, [10.0, 5.0]);
+        assert_eq!(profile.max_candela(), 10.0);
+        assert_eq!(profile.intensity(45.0, 0.0), 7.5);
+    }
+
+    #[test]
+    fn tilt_include() {
+        let text = "TILT=INCLUDE\n1\n2\n0 90\n1 1\n1 1000 1 2 1 1 1 0 0 0 1 1 100\n0 90\n0\n10 5\n";
+        let profile = IesProfile::parse(text).unwrap();
+        assert_eq!(profile.candela, [10.0, 5.0]);
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        let parse = |text: &str| IesProfile::parse(text).unwrap_err();
+        assert!(matches!(parse("IESNA:LM-63-2002\n"), IesError::MissingTilt));
+        assert!(matches!(
+            parse("TILT=lamp.tlt\n"),
+            IesError::UnsupportedTilt(_)
+        ));
+        assert!(matches!(
+            parse("TILT=NONE\n1 1000 1 2 1 1 1 0 0 0 1 1 100\n0 90\n0\n10\n"),
+            IesError::UnexpectedEnd
+        ));
+        assert!(matches!(
+            parse("TILT=NONE\n1 1000 1 1e20 1e20 1 1 0 0 0 1 1 100\n0\n0\n10\n"),
+            IesError::TooManyAngles
+        ));
+        assert!(matches!(
+            parse("TILT=NONE\n1 1000 1 2 1 1 1 0 0 0 1 1 100\n90 0\n0\n10 5\n"),
+            IesError::InvalidAngles
+        ));
+        assert!(matches!(
+            parse("TILT=NONE\n1 1000 1 2 1 2 1 0 0 0 1 1 100\n0 90\n0\n10 5\n"),
+            IesError::UnsupportedPhotometricType(2)
+        ));
+        assert!(matches!(
+            parse("TILT=NONE\n1 1000 1 2 1 1 1 0 0 0 1 1 100\n0 ninety\n0\n10 5\n"),
+            IesError::InvalidNumber(_)
+        ));
+    }
+
+    #[test]
+    fn axial_symmetry() {
+        let profile = profile(&[0.0, 90.0], &[0.0], &[10.0, 5.0]);
+        for horizontal_angle in [0.0, 45.0, 123.0, 270.0, -30.0] {
+            assert_eq!(profile.intensity(0.0, horizontal_angle), 10.0);
+            assert_eq!(profile.intensity(45.0, horizontal_angle), 7.5);
+        }
+    }
+
+    #[test]
+    fn quadrant_symmetry() {
+        let profile = profile(&[0.0, 90.0], &[0.0, 90.0], &[10.0, 10.0, 20.0, 20.0]);
+        assert_eq!(profile.intensity(0.0, 0.0), 10.0);
+        assert_eq!(profile.intensity(0.0, 90.0), 20.0);
+        assert_eq!(profile.intensity(0.0, 135.0), 15.0);
+        assert_eq!(profile.intensity(0.0, 180.0), 10.0);
+        assert_eq!(profile.intensity(0.0, 270.0), 20.0);
+        assert_eq!(profile.intensity(0.0, 315.0), 15.0);
+    }
+
+    #[test]
+    fn bilateral_symmetry() {
+        let profile = profile(
+            &[0.0, 90.0],
+            &[0.0, 90.0, 180.0],
+            &[10.0, 10.0, 20.0, 20.0, 30.0, 30.0],
+        );
+        assert_eq!(profile.intensity(0.0, 45.0), 15.0);
+        assert_eq!(profile.intensity(0.0, 180.0), 30.0);
+        assert_eq!(profile.intensity(0.0, 225.0), 25.0);
+        assert_eq!(profile.intensity(0.0, 270.0), 20.0);
+        assert_eq!(profile.intensity(0.0, -45.0), 15.0);
+    }
+
+    #[test]
+    fn out_of_range_vertical_angles() {
+        let downwards = profile(&[0.0, 90.0], &[0.0], &[10.0, 5.0]);
+        assert_eq!(downwards.intensity(90.0, 0.0), 5.0);
+        assert_eq!(downwards.intensity(120.0, 0.0), 0.0);
+        assert_eq!(downwards.intensity(180.0, 0.0), 0.0);
+
+        let upwards = profile(&[90.0, 180.0], &[0.0], &[5.0, 10.0]);
+        assert_eq!(upwards.intensity(45.0, 0.0), 0.0);
+        assert_eq!(upwards.intensity(180.0, 0.0), 10.0);
+    }
+}
@@ -1,17 +1,35 @@
 /*
 * TODO: implement brdf
 * TODO: implement material textures
-* TODO: implement bvh
 * TODO: implement default skybox and the ability to not use environment maps
 * TODO: better ui(live renderer)
 */
 
-pub use camera::Camera;
+pub use camera::{Camera, Projection};
 pub use environment::Environment;
-pub use geometry::{Geometry, Material, Triangle, Vertex};
-pub use renderer::{Renderer, RendererSettings};
+#[cfg(feature = "image")]
+pub use environment::EnvironmentError;
+#[cfg(feature = "obj")]
+pub use geometry::ObjError;
+pub use geometry::{
+    AlphaMode, EmissionUnit, Geometry, Instance, Light, LightKind, Material, Mesh, Primitive,
+    PrimitiveKind, Texture, Triangle, Vertex,
+};
+#[cfg(feature = "gltf")]
+pub use geometry::{GltfCamera, GltfError, GltfNodeInfo, GltfSceneInfo, GltfSelection};
+pub use geometry::{PlyError, StlError};
+pub use ies::{IesError, IesProfile};
+pub use renderer::{
+    AccumulationFormat, EnvironmentFormat, GpuMemoryUsage, Integrator, OutputFormat, Renderer,
+    RendererSettings,
+};
+pub use sky::Sky;
+pub use validation::ValidationError;
 
 mod camera;
 mod environment;
 mod geometry;
+mod ies;
 mod renderer;
+mod sky;
+mod validation;
@@ -0,0 +1,142 @@
+// the smaller formats are lossy and opt-in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnvironmentFormat {
+    #[default]
+    Rgba32Float,
+    // values are clamped to 65504, which clips unclipped suns in hdris and the baked sky
+    Rgba16Float,
+    // clamped to 65408 as well, with less precision in the smaller channels of a texel
+    Rgb9e5Ufloat,
+}
+
+impl EnvironmentFormat {
+    pub(crate) fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            EnvironmentFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+            EnvironmentFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            EnvironmentFormat::Rgb9e5Ufloat => wgpu::TextureFormat::Rgb9e5Ufloat,
+        }
+    }
+
+    pub(crate) fn bytes_per_pixel(self) -> u32 {
+        match self {
+            EnvironmentFormat::Rgba32Float => 16,
+            EnvironmentFormat::Rgba16Float => 8,
+            EnvironmentFormat::Rgb9e5Ufloat => 4,
+        }
+    }
+
+    // converts linear rgba32 pixels to the texel layout of the format
+    pub(crate) fn pack(self, pixels: &[glam::Vec4]) -> Vec<u8> {
+        match self {
+            EnvironmentFormat::Rgba32Float => bytemuck::cast_slice(pixels).to_vec(),
+            EnvironmentFormat::Rgba16Float => {
+                let texels: Vec<u16> = pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.to_array())
+                    .map(|value| half::f16::from_f32(value.min(half::f16::MAX.to_f32())).to_bits())
+                    .collect();
+                bytemuck::cast_slice(&texels).to_vec()
+            }
+            EnvironmentFormat::Rgb9e5Ufloat => {
+                let texels: Vec<u32> = pixels
+                    .iter()
+                    .map(|pixel| pack_rgb9e5(pixel.truncate()))
+                    .collect();
+                bytemuck::cast_slice(&texels).to_vec()
+            }
+        }
+    }
+}
+
+// the accumulation holds the mean of the samples, half floats keep about three decimal digits of it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccumulationFormat {
+    #[default]
+    Rgba32Float,
+    Rgba16Float,
+}
+
+impl AccumulationFormat {
+    pub(crate) fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            AccumulationFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+            AccumulationFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+// the output is tone mapped so 8 bits are enough for display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Rgba32Float,
+    Rgba16Float,
+    Rgba8Unorm,
+}
+
+impl OutputFormat {
+    pub(crate) fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            OutputFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+            OutputFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            OutputFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+// name of a storage texture format in wgsl
+pub(crate) fn wgsl_storage_format(format: wgpu::TextureFormat) -> &'static str {
+    match format {
+        wgpu::TextureFormat::Rgba32Float => "rgba32float",
+        wgpu::TextureFormat::Rgba16Float => "rgba16float",
+        wgpu::TextureFormat::Rgba8Unorm => "rgba8unorm",
+        _ => unreachable!("unsupported storage texture format {:?}", format),
+    }
+}
+
+// shared exponent packing from the EXT_texture_shared_exponent specification
+fn pack_rgb9e5(color: glam::Vec3) -> u32 {
+    const MANTISSA_BITS: i32 = 9;
+    const EXPONENT_BIAS: i32 = 15;
+    const MAX_VALUE: f32 = 511.0 / 512.0 * 65536.0;
+
+    let color = color.clamp(glam::Vec3::ZERO, glam::Vec3::splat(MAX_VALUE));
+    let max_component = color.max_element();
+    if max_component <= 0.0 {
+        return 0;
+    }
+
+    let mut exponent =
+        (max_component.log2().floor() as i32).max(-EXPONENT_BIAS - 1) + 1 + EXPONENT_BIAS;
+    let max_mantissa =
+        (max_component / 2f32.powi(exponent - EXPONENT_BIAS - MANTISSA_BITS) + 0.5).floor();
+    if max_mantissa as i32 == 1 << MANTISSA_BITS {
+        exponent += 1;
+    }
+
+    let scale = 2f32.powi(exponent - EXPONENT_BIAS - MANTISSA_BITS);
+    let mantissa = (color / scale + 0.5).floor().as_uvec3();
+    mantissa.x | mantissa.y << 9 | mantissa.z << 18 | (exponent as u32) << 27
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuMemoryUsage {
+    pub render_targets: u64,
+    pub environment: u64,
+    pub geometry: u64,
+    pub uniforms: u64,
+    // wgpu doesn't expose the memory of the adapter, only the size limit of a storage binding
+    pub largest_storage_buffer: u64,
+    pub max_storage_buffer: u64,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.render_targets + self.environment + self.geometry + self.uniforms
+    }
+
+    pub fn exceeds_limits(&self) -> bool {
+        self.largest_storage_buffer > self.max_storage_buffer
+    }
+}
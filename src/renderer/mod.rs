@@ -1,16 +1,26 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
     camera::{Camera, GpuCamera},
-    geometry::{Geometry, GpuMaterial, GpuTriangle, GpuVertex},
-    Environment,
+    environment,
+    geometry::{
+        self, BottomLevels, Geometry, GpuBvhNode, GpuInstance, GpuLight, GpuLightBvhNode,
+        GpuMaterial, GpuTriangle, GpuVertex,
+    },
+    Environment, Light, Material, ValidationError,
 };
 
+pub use self::formats::{AccumulationFormat, EnvironmentFormat, GpuMemoryUsage, OutputFormat};
+
 use self::{
     passes::{BlitPass, RaytracingPass},
     utils::{StorageBuffer, Texture2D, UniformBuffer},
 };
 
+mod formats;
 mod passes;
 mod utils;
 
@@ -19,17 +29,33 @@ pub struct Renderer {
     max_samples: u32,
     samples_per_render: u32,
     num_samples: u32,
+    environment_format: EnvironmentFormat,
+    accumulation_format: AccumulationFormat,
+    output_format: OutputFormat,
+    max_storage_buffer: u64,
     pre_render_cmds: PreRenderCommands,
+    // kept without textures to rebuild the top level bvh and the lights when instances move
+    geometry: Geometry,
+    num_textures: usize,
+    material_areas: Vec<f32>,
+    bottom_levels: BottomLevels,
     acc_input_texture: Texture2D,
     acc_output_texture: Texture2D,
     output_texture: Texture2D,
     settings_uniform: UniformBuffer<SettingsUniform>,
     per_render_uniform: UniformBuffer<PerRenderUniform>,
     camera_uniform: UniformBuffer<GpuCamera>,
+    environment_uniform: UniformBuffer<EnvironmentUniform>,
     environment_texture: Texture2D,
     materials_storage: StorageBuffer<GpuMaterial>,
     vertices_storage: StorageBuffer<GpuVertex>,
     triangles_storage: StorageBuffer<GpuTriangle>,
+    instances_storage: StorageBuffer<GpuInstance>,
+    texels_storage: StorageBuffer<u32>,
+    lights_storage: StorageBuffer<GpuLight>,
+    ies_texture: Texture2D,
+    light_nodes_storage: StorageBuffer<GpuLightBvhNode>,
+    bvh_nodes_storage: StorageBuffer<GpuBvhNode>,
     raytracing_pass: RaytracingPass,
     raytracing_bind_group: wgpu::BindGroup,
     blit_pass: BlitPass,
@@ -41,45 +67,24 @@ impl Renderer {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         size: glam::UVec2,
-        output_format: wgpu::TextureFormat,
+        surface_format: wgpu::TextureFormat,
         settings: RendererSettings,
         camera: Camera,
         environment: Environment,
-        geometry: Geometry,
-    ) -> Self {
-        let acc_input_texture = Texture2D::new(
+        mut geometry: Geometry,
+    ) -> Result<Self, ValidationError> {
+        settings.validate()?;
+        camera.validate()?;
+        environment.validate()?;
+        geometry.validate()?;
+
+        let (acc_input_texture, acc_output_texture, output_texture) = create_render_targets(
             device,
-            "accumulation_input_texture",
             size,
-            wgpu::TextureFormat::Rgba32Float,
-            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            1,
+            settings.accumulation_format,
+            settings.output_format,
         );
 
-        let acc_output_texture = Texture2D::new(
-            device,
-            "accumulation_output_texture",
-            size,
-            wgpu::TextureFormat::Rgba32Float,
-            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
-            1,
-        );
-
-        let output_texture = Texture2D::new(
-            device,
-            "output_texture",
-            size,
-            wgpu::TextureFormat::Rgba32Float,
-            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
-            1,
-        );
-
-        let settings = if settings.validate() {
-            settings
-        } else {
-            RendererSettings::default()
-        };
-
         let settings_uniform = UniformBuffer::new_with_data(
             device,
             "settings_uniform",
@@ -88,6 +93,9 @@ impl Renderer {
                 max_ray_depth: settings.max_ray_depth,
                 furnace_test: settings.furnace_test.into(),
                 environment_brightness: settings.environment_brightness,
+                exposure: settings.exposure,
+                integrator: settings.integrator as u32,
+                pad0: [0; 2],
             }],
         );
 
@@ -97,59 +105,34 @@ impl Renderer {
             &[PerRenderUniform::default()],
         );
 
-        let camera = if camera.validate() {
-            camera
-        } else {
-            Camera::default()
-        };
-
         let camera_uniform =
             UniformBuffer::new_with_data(device, "camera_uniform", &[GpuCamera::from(camera)]);
 
-        let environment = if environment.validate() {
-            environment
-        } else {
-            Environment::default()
-        };
-
-        let environment_texture = Texture2D::new(
+        let environment = prepare_environment(device, environment);
+        let environment_texture =
+            create_environment_texture(device, environment.size, settings.environment_format);
+        let environment_uniform = UniformBuffer::new_with_data(
             device,
-            "environment_texture",
-            environment.size,
-            wgpu::TextureFormat::Rgba32Float,
-            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            1,
-        );
-
-        queue.write_texture(
-            environment_texture.inner().as_image_copy(),
-            &environment.data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(environment.size.x * std::mem::size_of::<glam::Vec4>() as u32),
-                rows_per_image: Some(environment.size.y),
-            },
-            environment_texture.inner().size(),
+            "environment_uniform",
+            &[write_environment_texture(
+                queue,
+                &environment_texture,
+                &environment,
+                settings.environment_format,
+            )],
         );
 
-        let geometry = if geometry.validate() {
-            geometry
-        } else {
-            Geometry::default()
-        };
-
-        let gpu_materials: Vec<GpuMaterial> = geometry
-            .materials
-            .into_iter()
-            .map(GpuMaterial::from)
-            .collect();
-        let gpu_vertices: Vec<GpuVertex> =
-            geometry.vertices.into_iter().map(GpuVertex::from).collect();
-        let gpu_triangles: Vec<GpuTriangle> = geometry
-            .triangles
-            .into_iter()
-            .map(GpuTriangle::from)
-            .collect();
+        let material_areas = geometry.material_areas();
+        let gpu_materials = geometry::convert_materials(&geometry.materials, &material_areas);
+        let (gpu_lights, gpu_light_nodes) = geometry::convert_lights(&geometry);
+        let (gpu_triangles, gpu_instances, gpu_bvh_nodes, bottom_levels) =
+            build_acceleration_structure(&geometry);
+        let gpu_vertices = geometry::convert_vertices(&geometry.vertices);
+        let num_textures = geometry.textures.len();
+        let gpu_texels = geometry::pack_textures(std::mem::take(&mut geometry.textures));
+        let (ies_size, ies_data) = geometry::build_ies_tables(&geometry.ies_profiles);
+        let ies_texture = create_ies_texture(device, ies_size);
+        write_ies_texture(queue, &ies_texture, &ies_data);
 
         let materials_storage =
             StorageBuffer::new_with_data(device, "materials_storage", &gpu_materials);
@@ -157,8 +140,20 @@ impl Renderer {
             StorageBuffer::new_with_data(device, "vertices_storage", &gpu_vertices);
         let triangles_storage =
             StorageBuffer::new_with_data(device, "triangles_storage", &gpu_triangles);
-
-        let raytracing_pass = RaytracingPass::new(device);
+        let instances_storage =
+            StorageBuffer::new_with_data(device, "instances_storage", &gpu_instances);
+        let texels_storage = StorageBuffer::new_with_data(device, "texels_storage", &gpu_texels);
+        let lights_storage = StorageBuffer::new_with_data(device, "lights_storage", &gpu_lights);
+        let light_nodes_storage =
+            StorageBuffer::new_with_data(device, "light_nodes_storage", &gpu_light_nodes);
+        let bvh_nodes_storage =
+            StorageBuffer::new_with_data(device, "bvh_nodes_storage", &gpu_bvh_nodes);
+
+        let raytracing_pass = RaytracingPass::new(
+            device,
+            settings.accumulation_format.texture_format(),
+            settings.output_format.texture_format(),
+        );
         let raytracing_bind_group = raytracing_pass.create_bind_group(
             device,
             &acc_input_texture,
@@ -167,36 +162,59 @@ impl Renderer {
             &settings_uniform,
             &per_render_uniform,
             &camera_uniform,
+            &environment_uniform,
             &environment_texture,
             &materials_storage,
             &vertices_storage,
             &triangles_storage,
+            &instances_storage,
+            &texels_storage,
+            &lights_storage,
+            &ies_texture,
+            &light_nodes_storage,
+            &bvh_nodes_storage,
         );
 
-        let blit_pass = BlitPass::new(device, output_format);
+        let blit_pass = BlitPass::new(device, surface_format);
         let blit_bind_group = blit_pass.create_bind_group(device, &output_texture);
 
-        Self {
+        Ok(Self {
             size,
             max_samples: settings.max_samples,
             samples_per_render: settings.samples_per_render,
             num_samples: 0,
+            environment_format: settings.environment_format,
+            accumulation_format: settings.accumulation_format,
+            output_format: settings.output_format,
+            max_storage_buffer: (device.limits().max_storage_buffer_binding_size as u64)
+                .min(device.limits().max_buffer_size),
             pre_render_cmds: PreRenderCommands::default(),
+            geometry,
+            num_textures,
+            material_areas,
+            bottom_levels,
             acc_input_texture,
             acc_output_texture,
             output_texture,
             settings_uniform,
             per_render_uniform,
             camera_uniform,
+            environment_uniform,
             environment_texture,
             materials_storage,
             vertices_storage,
             triangles_storage,
+            instances_storage,
+            texels_storage,
+            lights_storage,
+            ies_texture,
+            light_nodes_storage,
+            bvh_nodes_storage,
             raytracing_pass,
             raytracing_bind_group,
             blit_pass,
             blit_bind_group,
-        }
+        })
     }
 
     pub fn reset(&mut self) {
@@ -208,19 +226,179 @@ impl Renderer {
         self.pre_render_cmds.resize = Some(new_size);
     }
 
-    pub fn update_settings(&mut self, settings: RendererSettings) {
+    // the update functions validate immediately and leave the renderer untouched on errors
+    pub fn update_settings(&mut self, settings: RendererSettings) -> Result<(), ValidationError> {
+        settings.validate()?;
         self.pre_render_cmds.reset = true;
         self.pre_render_cmds.update_settings = Some(settings);
+        Ok(())
     }
 
-    pub fn update_camera(&mut self, camera: Camera) {
+    pub fn update_camera(&mut self, camera: Camera) -> Result<(), ValidationError> {
+        camera.validate()?;
         self.pre_render_cmds.reset = true;
         self.pre_render_cmds.update_camera = Some(camera);
+        Ok(())
+    }
+
+    pub fn update_environment(&mut self, environment: Environment) -> Result<(), ValidationError> {
+        environment.validate()?;
+        self.pre_render_cmds.reset = true;
+        self.pre_render_cmds.update_environment = Some(environment);
+        Ok(())
     }
 
-    pub fn update_geometry(&mut self, geometry: Geometry) {
+    pub fn update_geometry(&mut self, geometry: Geometry) -> Result<(), ValidationError> {
+        geometry.validate()?;
         self.pre_render_cmds.reset = true;
         self.pre_render_cmds.update_geometry = Some(geometry);
+        // the new geometry is uploaded as a whole
+        self.pre_render_cmds.update_instances = false;
+        self.pre_render_cmds.update_materials = None;
+        self.pre_render_cmds.update_lights = false;
+        Ok(())
+    }
+
+    // edits materials without uploading the geometry again, the lights are only converted again
+    // if an edited material is or was emissive
+    pub fn update_material(
+        &mut self,
+        index: usize,
+        material: Material,
+    ) -> Result<(), ValidationError> {
+        self.replace_materials(index, std::slice::from_ref(&material))
+    }
+
+    // replaces all materials, their number can't change
+    pub fn update_materials(&mut self, materials: &[Material]) -> Result<(), ValidationError> {
+        let expected = match &self.pre_render_cmds.update_geometry {
+            Some(geometry) => geometry.materials.len(),
+            None => self.geometry.materials.len(),
+        };
+        if materials.len() != expected {
+            return Err(ValidationError::MaterialCount {
+                expected,
+                len: materials.len(),
+            });
+        }
+        self.replace_materials(0, materials)
+    }
+
+    fn replace_materials(
+        &mut self,
+        first: usize,
+        materials: &[Material],
+    ) -> Result<(), ValidationError> {
+        let pending_geometry = self.pre_render_cmds.update_geometry.is_some();
+        let (geometry, num_textures) = match self.pre_render_cmds.update_geometry.as_mut() {
+            Some(geometry) => {
+                let num_textures = geometry.textures.len();
+                (geometry, num_textures)
+            }
+            None => (&mut self.geometry, self.num_textures),
+        };
+        let range = first..first + materials.len();
+        if range.end > geometry.materials.len() {
+            return Err(ValidationError::MaterialIndexOutOfRange {
+                material: range.end - 1,
+            });
+        }
+        for (i, material) in materials.iter().enumerate() {
+            geometry::validate_material(first + i, material, num_textures)?;
+        }
+
+        let targets = &mut geometry.materials[range.clone()];
+        let emissive = targets.iter().chain(materials).any(Material::is_emissive);
+        targets.clone_from_slice(materials);
+
+        self.pre_render_cmds.reset = true;
+        if !pending_geometry {
+            self.pre_render_cmds.update_materials =
+                Some(match self.pre_render_cmds.update_materials.take() {
+                    Some(pending) => pending.start.min(range.start)..pending.end.max(range.end),
+                    None => range,
+                });
+            self.pre_render_cmds.update_lights |= emissive;
+        }
+        Ok(())
+    }
+
+    // moves an instance without uploading the geometry again, only the top level bvh is rebuilt
+    // and the lights are only converted again if the instance is emissive
+    pub fn update_instance_transform(
+        &mut self,
+        instance: usize,
+        transform: glam::Mat4,
+    ) -> Result<(), ValidationError> {
+        let pending_geometry = self.pre_render_cmds.update_geometry.is_some();
+        let geometry = self
+            .pre_render_cmds
+            .update_geometry
+            .as_mut()
+            .unwrap_or(&mut self.geometry);
+        if instance >= geometry.instances.len() {
+            return Err(ValidationError::InstanceOutOfRange { instance });
+        }
+        if !geometry::is_valid_transform(transform) {
+            return Err(ValidationError::InstanceTransform { instance });
+        }
+        geometry.instances[instance].transform = transform;
+        let emissive = geometry.is_instance_emissive(instance);
+
+        self.pre_render_cmds.reset = true;
+        if !pending_geometry {
+            self.pre_render_cmds.update_instances = true;
+            self.pre_render_cmds.update_lights |= emissive;
+        }
+        Ok(())
+    }
+
+    // replaces all lights without uploading the geometry again
+    pub fn update_lights(&mut self, lights: &[Light]) -> Result<(), ValidationError> {
+        let pending_geometry = self.pre_render_cmds.update_geometry.is_some();
+        let geometry = self
+            .pre_render_cmds
+            .update_geometry
+            .as_mut()
+            .unwrap_or(&mut self.geometry);
+        for (index, light) in lights.iter().enumerate() {
+            geometry::validate_light(index, light, geometry.ies_profiles.len())?;
+        }
+        geometry.lights = lights.to_vec();
+
+        self.pre_render_cmds.reset = true;
+        if !pending_geometry {
+            self.pre_render_cmds.update_lights = true;
+        }
+        Ok(())
+    }
+
+    // memory of the textures and buffers currently allocated by the renderer, budgeted against
+    // the storage buffer limit of the device
+    pub fn memory_usage(&self) -> GpuMemoryUsage {
+        let storage_buffers = [
+            self.materials_storage.size_in_bytes(),
+            self.vertices_storage.size_in_bytes(),
+            self.triangles_storage.size_in_bytes(),
+            self.instances_storage.size_in_bytes(),
+            self.texels_storage.size_in_bytes(),
+            self.lights_storage.size_in_bytes(),
+            self.light_nodes_storage.size_in_bytes(),
+            self.bvh_nodes_storage.size_in_bytes(),
+        ];
+        GpuMemoryUsage {
+            render_targets: self.acc_input_texture.size_in_bytes()
+                + self.acc_output_texture.size_in_bytes()
+                + self.output_texture.size_in_bytes(),
+            environment: self.environment_texture.size_in_bytes(),
+            geometry: storage_buffers.iter().sum::<u64>() + self.ies_texture.size_in_bytes(),
+            uniforms: self.settings_uniform.size_in_bytes()
+                + self.per_render_uniform.size_in_bytes()
+                + self.camera_uniform.size_in_bytes()
+                + self.environment_uniform.size_in_bytes(),
+            largest_storage_buffer: storage_buffers.into_iter().max().unwrap_or(0),
+            max_storage_buffer: self.max_storage_buffer,
+        }
     }
 
     pub fn render(
@@ -231,6 +409,7 @@ impl Renderer {
         view: &wgpu::TextureView,
     ) {
         let mut update_bind_groups = false;
+        let mut recreate_render_targets = false;
 
         if self.pre_render_cmds.reset {
             self.num_samples = 0;
@@ -248,48 +427,24 @@ impl Renderer {
             );
         }
 
-        if let Some(new_size) = self.pre_render_cmds.resize.take() {
-            self.size = new_size.max(glam::UVec2::ONE);
-
-            self.acc_input_texture = Texture2D::new(
-                device,
-                "accumulation_input_texture",
-                self.size,
-                wgpu::TextureFormat::Rgba32Float,
-                wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                1,
-            );
-
-            self.acc_output_texture = Texture2D::new(
-                device,
-                "accumulation_output_texture",
-                self.size,
-                wgpu::TextureFormat::Rgba32Float,
-                wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
-                1,
-            );
-
-            self.output_texture = Texture2D::new(
-                device,
-                "output_texture",
-                self.size,
-                wgpu::TextureFormat::Rgba32Float,
-                wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
-                1,
-            );
-
-            update_bind_groups = true;
-        }
-
         if let Some(settings) = self.pre_render_cmds.update_settings.take() {
-            let settings = if settings.validate() {
-                settings
-            } else {
-                RendererSettings::default()
-            };
-
             self.max_samples = settings.max_samples;
             self.samples_per_render = settings.samples_per_render;
+            // applies to the next uploaded environment
+            self.environment_format = settings.environment_format;
+
+            if settings.accumulation_format != self.accumulation_format
+                || settings.output_format != self.output_format
+            {
+                self.accumulation_format = settings.accumulation_format;
+                self.output_format = settings.output_format;
+                self.raytracing_pass = RaytracingPass::new(
+                    device,
+                    self.accumulation_format.texture_format(),
+                    self.output_format.texture_format(),
+                );
+                recreate_render_targets = true;
+            }
 
             self.settings_uniform.write(
                 queue,
@@ -298,39 +453,67 @@ impl Renderer {
                     max_ray_depth: settings.max_ray_depth,
                     furnace_test: settings.furnace_test.into(),
                     environment_brightness: settings.environment_brightness,
+                    exposure: settings.exposure,
+                    integrator: settings.integrator as u32,
+                    pad0: [0; 2],
                 }],
             );
         }
 
-        if let Some(camera) = self.pre_render_cmds.update_camera.take() {
-            let camera = if camera.validate() {
-                camera
-            } else {
-                Camera::default()
-            };
+        if let Some(new_size) = self.pre_render_cmds.resize.take() {
+            self.size = new_size.max(glam::UVec2::ONE);
+            recreate_render_targets = true;
+        }
+
+        if recreate_render_targets {
+            (
+                self.acc_input_texture,
+                self.acc_output_texture,
+                self.output_texture,
+            ) = create_render_targets(
+                device,
+                self.size,
+                self.accumulation_format,
+                self.output_format,
+            );
 
+            update_bind_groups = true;
+        }
+
+        if let Some(camera) = self.pre_render_cmds.update_camera.take() {
             self.camera_uniform.write(queue, &[GpuCamera::from(camera)]);
         }
 
-        if let Some(geometry) = self.pre_render_cmds.update_geometry.take() {
-            let geometry = if geometry.validate() {
-                geometry
-            } else {
-                Geometry::default()
-            };
-
-            let gpu_materials: Vec<GpuMaterial> = geometry
-                .materials
-                .into_iter()
-                .map(GpuMaterial::from)
-                .collect();
-            let gpu_vertices: Vec<GpuVertex> =
-                geometry.vertices.into_iter().map(GpuVertex::from).collect();
-            let gpu_triangles: Vec<GpuTriangle> = geometry
-                .triangles
-                .into_iter()
-                .map(GpuTriangle::from)
-                .collect();
+        if let Some(environment) = self.pre_render_cmds.update_environment.take() {
+            let environment = prepare_environment(device, environment);
+
+            let texture = self.environment_texture.inner();
+            if environment.size != glam::uvec2(texture.width(), texture.height())
+                || self.environment_format.texture_format() != texture.format()
+            {
+                self.environment_texture =
+                    create_environment_texture(device, environment.size, self.environment_format);
+                update_bind_groups = true;
+            }
+            let environment_uniform = write_environment_texture(
+                queue,
+                &self.environment_texture,
+                &environment,
+                self.environment_format,
+            );
+            self.environment_uniform
+                .write(queue, &[environment_uniform]);
+        }
+
+        if let Some(mut geometry) = self.pre_render_cmds.update_geometry.take() {
+            let material_areas = geometry.material_areas();
+            let gpu_materials = geometry::convert_materials(&geometry.materials, &material_areas);
+            let (gpu_lights, gpu_light_nodes) = geometry::convert_lights(&geometry);
+            let (gpu_triangles, gpu_instances, gpu_bvh_nodes, bottom_levels) =
+                build_acceleration_structure(&geometry);
+            let gpu_vertices = geometry::convert_vertices(&geometry.vertices);
+            let num_textures = geometry.textures.len();
+            let gpu_texels = geometry::pack_textures(std::mem::take(&mut geometry.textures));
 
             if gpu_materials.len() != self.materials_storage.len() {
                 self.materials_storage =
@@ -355,6 +538,105 @@ impl Renderer {
             } else {
                 self.triangles_storage.write(queue, &gpu_triangles);
             }
+
+            if gpu_instances.len() != self.instances_storage.len() {
+                self.instances_storage =
+                    StorageBuffer::new_with_data(device, "instances_storage", &gpu_instances);
+                update_bind_groups = true;
+            } else {
+                self.instances_storage.write(queue, &gpu_instances);
+            }
+
+            if gpu_bvh_nodes.len() != self.bvh_nodes_storage.len() {
+                self.bvh_nodes_storage =
+                    StorageBuffer::new_with_data(device, "bvh_nodes_storage", &gpu_bvh_nodes);
+                update_bind_groups = true;
+            } else {
+                self.bvh_nodes_storage.write(queue, &gpu_bvh_nodes);
+            }
+
+            if gpu_texels.len() != self.texels_storage.len() {
+                self.texels_storage =
+                    StorageBuffer::new_with_data(device, "texels_storage", &gpu_texels);
+                update_bind_groups = true;
+            } else {
+                self.texels_storage.write(queue, &gpu_texels);
+            }
+
+            if gpu_lights.len() != self.lights_storage.len() {
+                self.lights_storage =
+                    StorageBuffer::new_with_data(device, "lights_storage", &gpu_lights);
+                update_bind_groups = true;
+            } else {
+                self.lights_storage.write(queue, &gpu_lights);
+            }
+
+            if gpu_light_nodes.len() != self.light_nodes_storage.len() {
+                self.light_nodes_storage =
+                    StorageBuffer::new_with_data(device, "light_nodes_storage", &gpu_light_nodes);
+                update_bind_groups = true;
+            } else {
+                self.light_nodes_storage.write(queue, &gpu_light_nodes);
+            }
+
+            let (ies_size, ies_data) = geometry::build_ies_tables(&geometry.ies_profiles);
+            if ies_size.y != self.ies_texture.inner().height() {
+                self.ies_texture = create_ies_texture(device, ies_size);
+                update_bind_groups = true;
+            }
+            write_ies_texture(queue, &self.ies_texture, &ies_data);
+
+            self.geometry = geometry;
+            self.num_textures = num_textures;
+            self.material_areas = material_areas;
+            self.bottom_levels = bottom_levels;
+        }
+
+        if self.pre_render_cmds.update_instances {
+            self.pre_render_cmds.update_instances = false;
+
+            // the bottom levels stay as they are, the top level always fits in front of them
+            let (gpu_instances, top_level_nodes) =
+                geometry::build_top_level(&self.geometry, &self.bottom_levels);
+            self.instances_storage.write(queue, &gpu_instances);
+            self.bvh_nodes_storage.write_at(queue, 0, &top_level_nodes);
+        }
+
+        if let Some(range) = self.pre_render_cmds.update_materials.take() {
+            let gpu_materials = geometry::convert_materials(
+                &self.geometry.materials[range.clone()],
+                &self.material_areas[range.clone()],
+            );
+            self.materials_storage
+                .write_at(queue, range.start, &gpu_materials);
+        }
+
+        if self.pre_render_cmds.update_lights {
+            self.pre_render_cmds.update_lights = false;
+
+            // moving scaled instances changes the areas that the emission is spread over
+            self.material_areas = self.geometry.material_areas();
+            self.materials_storage.write(
+                queue,
+                &geometry::convert_materials(&self.geometry.materials, &self.material_areas),
+            );
+
+            let (gpu_lights, gpu_light_nodes) = geometry::convert_lights(&self.geometry);
+            if gpu_lights.len() != self.lights_storage.len() {
+                self.lights_storage =
+                    StorageBuffer::new_with_data(device, "lights_storage", &gpu_lights);
+                update_bind_groups = true;
+            } else {
+                self.lights_storage.write(queue, &gpu_lights);
+            }
+
+            if gpu_light_nodes.len() != self.light_nodes_storage.len() {
+                self.light_nodes_storage =
+                    StorageBuffer::new_with_data(device, "light_nodes_storage", &gpu_light_nodes);
+                update_bind_groups = true;
+            } else {
+                self.light_nodes_storage.write(queue, &gpu_light_nodes);
+            }
         }
 
         if update_bind_groups {
@@ -366,10 +648,17 @@ impl Renderer {
                 &self.settings_uniform,
                 &self.per_render_uniform,
                 &self.camera_uniform,
+                &self.environment_uniform,
                 &self.environment_texture,
                 &self.materials_storage,
                 &self.vertices_storage,
                 &self.triangles_storage,
+                &self.instances_storage,
+                &self.texels_storage,
+                &self.lights_storage,
+                &self.ies_texture,
+                &self.light_nodes_storage,
+                &self.bvh_nodes_storage,
             );
 
             self.blit_bind_group = self
@@ -408,6 +697,156 @@ impl Renderer {
     }
 }
 
+fn create_render_targets(
+    device: &wgpu::Device,
+    size: glam::UVec2,
+    accumulation_format: AccumulationFormat,
+    output_format: OutputFormat,
+) -> (Texture2D, Texture2D, Texture2D) {
+    let acc_input_texture = Texture2D::new(
+        device,
+        "accumulation_input_texture",
+        size,
+        accumulation_format.texture_format(),
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        1,
+    );
+
+    let acc_output_texture = Texture2D::new(
+        device,
+        "accumulation_output_texture",
+        size,
+        accumulation_format.texture_format(),
+        wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        1,
+    );
+
+    let output_texture = Texture2D::new(
+        device,
+        "output_texture",
+        size,
+        output_format.texture_format(),
+        wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        1,
+    );
+
+    (acc_input_texture, acc_output_texture, output_texture)
+}
+
+// downscales the environment to the device limits
+fn prepare_environment(device: &wgpu::Device, environment: Environment) -> Environment {
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    if environment.size.max_element() > max_dimension {
+        log::warn!(
+            "environment of {}x{} exceeds the maximum texture size of {}, downscaling it",
+            environment.size.x,
+            environment.size.y,
+            max_dimension
+        );
+        environment.downscaled(max_dimension)
+    } else {
+        environment
+    }
+}
+
+fn create_environment_texture(
+    device: &wgpu::Device,
+    size: glam::UVec2,
+    format: EnvironmentFormat,
+) -> Texture2D {
+    Texture2D::new(
+        device,
+        "environment_texture",
+        size,
+        format.texture_format(),
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        32 - size.max_element().leading_zeros(),
+    )
+}
+
+// returns the diffuse irradiance of the preview, projected from the first level that is at most
+// 256 texels wide
+fn write_environment_texture(
+    queue: &wgpu::Queue,
+    environment_texture: &Texture2D,
+    environment: &Environment,
+    format: EnvironmentFormat,
+) -> EnvironmentUniform {
+    let mut irradiance_sh = None;
+    // box filtered levels stand in for prefiltering, the preview picks the level by roughness
+    for (mip_level, (size, pixels)) in environment.mip_chain().into_iter().enumerate() {
+        if irradiance_sh.is_none() && size.x <= 256 {
+            irradiance_sh = Some(environment::irradiance_sh(size, &pixels));
+        }
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: environment_texture.inner(),
+                mip_level: mip_level as u32,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &format.pack(&pixels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.x * format.bytes_per_pixel()),
+                rows_per_image: Some(size.y),
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // the chain always ends with a 1x1 level
+    EnvironmentUniform {
+        irradiance_sh: irradiance_sh
+            .unwrap()
+            .map(|coefficient| coefficient.extend(0.0)),
+    }
+}
+
+// the top level bvh is written into the room reserved in front of the bottom levels
+fn build_acceleration_structure(
+    geometry: &Geometry,
+) -> (
+    Vec<GpuTriangle>,
+    Vec<GpuInstance>,
+    Vec<GpuBvhNode>,
+    BottomLevels,
+) {
+    let (gpu_triangles, mut gpu_bvh_nodes, bottom_levels) = geometry::build_bottom_levels(geometry);
+    let (gpu_instances, top_level_nodes) = geometry::build_top_level(geometry, &bottom_levels);
+    gpu_bvh_nodes[..top_level_nodes.len()].copy_from_slice(&top_level_nodes);
+    (gpu_triangles, gpu_instances, gpu_bvh_nodes, bottom_levels)
+}
+
+fn create_ies_texture(device: &wgpu::Device, size: glam::UVec2) -> Texture2D {
+    Texture2D::new(
+        device,
+        "ies_texture",
+        size,
+        wgpu::TextureFormat::R32Float,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        1,
+    )
+}
+
+fn write_ies_texture(queue: &wgpu::Queue, ies_texture: &Texture2D, data: &[f32]) {
+    let size = ies_texture.inner().size();
+    queue.write_texture(
+        ies_texture.inner().as_image_copy(),
+        bytemuck::cast_slice(data),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(size.width * std::mem::size_of::<f32>() as u32),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+}
+
 #[derive(Clone, Debug)]
 pub struct RendererSettings {
     pub samples_per_render: u32,
@@ -415,6 +854,12 @@ pub struct RendererSettings {
     pub max_ray_depth: u32,
     pub furnace_test: bool,
     pub environment_brightness: f32,
+    pub exposure: f32,
+    pub integrator: Integrator,
+    // the environment format applies to environments uploaded after the settings change
+    pub environment_format: EnvironmentFormat,
+    pub accumulation_format: AccumulationFormat,
+    pub output_format: OutputFormat,
 }
 
 impl Default for RendererSettings {
@@ -425,26 +870,55 @@ impl Default for RendererSettings {
             max_ray_depth: 10,
             furnace_test: false,
             environment_brightness: 1.0,
+            exposure: 1.0,
+            integrator: Integrator::default(),
+            environment_format: EnvironmentFormat::default(),
+            accumulation_format: AccumulationFormat::default(),
+            output_format: OutputFormat::default(),
         }
     }
 }
 
 impl RendererSettings {
-    pub fn validate(&self) -> bool {
-        self.samples_per_render > 0
-            && self.max_ray_depth > 0
-            && self.max_samples > 0
-            && self.environment_brightness > 0.0
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.samples_per_render == 0 || self.max_samples == 0 {
+            return Err(ValidationError::Settings(
+                "samples per render and max samples must be positive",
+            ));
+        }
+        if self.max_ray_depth == 0 {
+            return Err(ValidationError::Settings("max ray depth must be positive"));
+        }
+        let positive = |value: f32| value > 0.0;
+        if !positive(self.environment_brightness) || !positive(self.exposure) {
+            return Err(ValidationError::Settings(
+                "environment brightness and exposure must be positive",
+            ));
+        }
+        Ok(())
     }
 }
 
+// the path tracer is the reference, the preview only adds direct light and image based lighting
+// from the prefiltered environment to the first hit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    #[default]
+    PathTracer = 0,
+    Preview = 1,
+}
+
 #[derive(Default)]
 struct PreRenderCommands {
     reset: bool,
     resize: Option<glam::UVec2>,
     update_settings: Option<RendererSettings>,
     update_camera: Option<Camera>,
+    update_environment: Option<Environment>,
     update_geometry: Option<Geometry>,
+    update_instances: bool,
+    update_materials: Option<Range<usize>>,
+    update_lights: bool,
 }
 
 #[repr(C)]
@@ -454,6 +928,16 @@ pub struct SettingsUniform {
     max_ray_depth: u32,
     furnace_test: u32,
     environment_brightness: f32,
+    exposure: f32,
+    integrator: u32,
+    pad0: [u32; 2],
+}
+
+// irradiance over pi as l2 spherical harmonics, vec4 keeps the uniform array stride
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniform {
+    irradiance_sh: [glam::Vec4; 9],
 }
 
 #[repr(C)]
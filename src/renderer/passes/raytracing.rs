@@ -1,9 +1,12 @@
 use crate::{
     camera::GpuCamera,
-    geometry::{GpuMaterial, GpuTriangle, GpuVertex},
+    geometry::{
+        GpuBvhNode, GpuInstance, GpuLight, GpuLightBvhNode, GpuMaterial, GpuTriangle, GpuVertex,
+    },
     renderer::{
+        formats,
         utils::{self, StorageBuffer, Texture2D, UniformBuffer},
-        PerRenderUniform, SettingsUniform,
+        EnvironmentUniform, PerRenderUniform, SettingsUniform,
     },
 };
 
@@ -13,8 +16,28 @@ pub struct RaytracingPass {
 }
 
 impl RaytracingPass {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("raytracing.wgsl"));
+    pub fn new(
+        device: &wgpu::Device,
+        accumulation_format: wgpu::TextureFormat,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        // the storage texture formats are part of the shader source
+        let mut source = include_str!("raytracing.wgsl").to_owned();
+        for (token, format) in [
+            ("{{ACC_FORMAT}}", accumulation_format),
+            ("{{OUTPUT_FORMAT}}", output_format),
+        ] {
+            assert!(
+                source.contains(token),
+                "missing {} in raytracing.wgsl",
+                token
+            );
+            source = source.replace(token, formats::wgsl_storage_format(format));
+        }
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("raytracing.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("bind_group_layout_raytracing_pass"),
@@ -34,7 +57,7 @@ impl RaytracingPass {
                     visibility: wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::StorageTexture {
                         access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: wgpu::TextureFormat::Rgba32Float,
+                        format: accumulation_format,
                         view_dimension: wgpu::TextureViewDimension::D2,
                     },
                     count: None,
@@ -44,7 +67,7 @@ impl RaytracingPass {
                     visibility: wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::StorageTexture {
                         access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: wgpu::TextureFormat::Rgba32Float,
+                        format: output_format,
                         view_dimension: wgpu::TextureViewDimension::D2,
                     },
                     count: None,
@@ -119,6 +142,76 @@ impl RaytracingPass {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 13,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 14,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 16,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -151,10 +244,17 @@ impl RaytracingPass {
         settings_uniform: &UniformBuffer<SettingsUniform>,
         per_render_uniform: &UniformBuffer<PerRenderUniform>,
         camera_uniform: &UniformBuffer<GpuCamera>,
+        environment_uniform: &UniformBuffer<EnvironmentUniform>,
         environment_texture: &Texture2D,
         materials_storage: &StorageBuffer<GpuMaterial>,
         vertices_storage: &StorageBuffer<GpuVertex>,
         triangles_storage: &StorageBuffer<GpuTriangle>,
+        instances_storage: &StorageBuffer<GpuInstance>,
+        texels_storage: &StorageBuffer<u32>,
+        lights_storage: &StorageBuffer<GpuLight>,
+        ies_texture: &Texture2D,
+        light_nodes_storage: &StorageBuffer<GpuLightBvhNode>,
+        bvh_nodes_storage: &StorageBuffer<GpuBvhNode>,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("bind_group_raytracing_pass"),
@@ -200,6 +300,34 @@ impl RaytracingPass {
                     binding: 9,
                     resource: triangles_storage.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: instances_storage.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: texels_storage.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: lights_storage.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: wgpu::BindingResource::TextureView(ies_texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: light_nodes_storage.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: bvh_nodes_storage.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 16,
+                    resource: environment_uniform.as_entire_binding(),
+                },
             ],
         })
     }
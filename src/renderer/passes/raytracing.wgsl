@@ -3,6 +3,8 @@ struct Settings {
     max_ray_depth: u32,
     furnace_test: u32,
     environment_brightness: f32,
+    exposure: f32,
+    integrator: u32,
 }
 
 struct PerRender {
@@ -10,8 +12,13 @@ struct PerRender {
     current_time: u32,
 }
 
+struct Environment {
+    irradiance_sh: array<vec4<f32>, 9>,
+}
+
 struct Camera {
     position: vec3<f32>,
+    orthographic: u32,
     inverse_projection: mat4x4<f32>,
     inverse_view: mat4x4<f32>,
 }
@@ -19,14 +26,37 @@ struct Camera {
 struct Material {
     albedo: vec3<f32>,
     roughness: f32,
-    metallic: f32,
     emission: vec3<f32>,
+    metallic: f32,
+    absorption: vec3<f32>,
+    ior: f32,
+    transmission: f32,
+    thin_walled: u32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    sheen_color: vec3<f32>,
+    sheen_roughness: f32,
+    specular_color: vec3<f32>,
+    specular: f32,
+    alpha: f32,
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    base_color_texture: u32,
+    base_color_tex_coord: u32,
+    normal_texture: u32,
+    normal_tex_coord: u32,
+    normal_scale: f32,
+    double_sided: u32,
 }
 
 struct Vertex {
     position: vec3<f32>,
+    color_rg: u32,
     tex_coord: vec2<f32>,
+    tex_coord_1: vec2<f32>,
     normal: vec3<f32>,
+    color_ba: u32,
+    tangent: vec4<f32>,
 }
 
 struct Triangle {
@@ -34,21 +64,100 @@ struct Triangle {
     material_index: u32,
 }
 
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    cos_inner_angle: f32,
+    intensity: vec3<f32>,
+    cos_outer_angle: f32,
+    edge_u: vec3<f32>,
+    radius: f32,
+    edge_v: vec3<f32>,
+    flags: u32,
+    ies_profile: u32,
+}
+
+struct LightBvhNode {
+    bounds_min: vec3<f32>,
+    power: f32,
+    bounds_max: vec3<f32>,
+    cos_theta_o: f32,
+    direction: vec3<f32>,
+    cos_theta_e: f32,
+    index: u32,
+    flags: u32,
+}
+
+struct Texture {
+    offset: u32,
+    width: u32,
+    height: u32,
+    srgb: u32,
+}
+
+struct Instance {
+    transform: mat4x4<f32>,
+    inverse_transform: mat4x4<f32>,
+    blas_root: u32,
+    material_index: u32,
+    kind: u32,
+}
+
+struct BvhNode {
+    min: vec3<f32>,
+    // first item for leaves, first of the two children otherwise
+    first: u32,
+    max: vec3<f32>,
+    // zero for interior nodes
+    count: u32,
+}
+
 const INF: f32 = 4294967296.0;
 const PI: f32 = 3.1415926535897932384626433832795;
 const EPSILON: f32 = 0.00001;
 
+const ALPHA_MODE_OPAQUE: u32 = 0u;
+const ALPHA_MODE_MASK: u32 = 1u;
+const ALPHA_MODE_BLEND: u32 = 2u;
+const NO_TEXTURE: u32 = 0xffffffffu;
+const NO_MATERIAL: u32 = 0xffffffffu;
+// enough for the bvh depth limit of the builder (MAX_DEPTH in bvh.rs)
+const BVH_STACK_SIZE: u32 = 32u;
+const INSTANCE_KIND_MESH: u32 = 0u;
+const INSTANCE_KIND_SPHERE: u32 = 1u;
+const INSTANCE_KIND_PLANE: u32 = 2u;
+const INSTANCE_KIND_BOX: u32 = 3u;
+const INSTANCE_KIND_DISK: u32 = 4u;
+
+const LIGHT_KIND_POINT: u32 = 0u;
+const LIGHT_KIND_SPOT: u32 = 1u;
+const LIGHT_KIND_DIRECTIONAL: u32 = 2u;
+const LIGHT_KIND_RECT: u32 = 3u;
+const LIGHT_KIND_DISK: u32 = 4u;
+const LIGHT_KIND_SPHERE: u32 = 5u;
+const LIGHT_KIND_TRIANGLE: u32 = 6u;
+const LIGHT_FLAG_TWO_SIDED: u32 = 1u;
+const LIGHT_FLAG_VISIBLE: u32 = 2u;
+const NO_LIGHT: u32 = 0xffffffffu;
+
+const INTEGRATOR_PREVIEW: u32 = 1u;
+const NO_IES_PROFILE: u32 = 0xffffffffu;
+const IES_TABLE_HEIGHT: u32 = 128u;
+const LIGHT_NODE_FLAG_LEAF: u32 = 1u;
+const LIGHT_NODE_FLAG_TWO_SIDED: u32 = 2u;
+
 @group(0)
 @binding(0)
 var t_acc_input: texture_2d<f32>;
 
 @group(0)
 @binding(1)
-var t_acc_output: texture_storage_2d<rgba32float, write>;
+var t_acc_output: texture_storage_2d<{{ACC_FORMAT}}, write>;
 
 @group(0)
 @binding(2)
-var t_output: texture_storage_2d<rgba32float, write>;
+var t_output: texture_storage_2d<{{OUTPUT_FORMAT}}, write>;
 
 @group(0)
 @binding(3)
@@ -78,6 +187,34 @@ var<storage, read> b_vertices: array<Vertex>;
 @binding(9)
 var<storage, read> b_triangles: array<Triangle>;
 
+@group(0)
+@binding(10)
+var<storage, read> b_instances: array<Instance>;
+
+@group(0)
+@binding(11)
+var<storage, read> b_texels: array<u32>;
+
+@group(0)
+@binding(12)
+var<storage, read> b_lights: array<Light>;
+
+@group(0)
+@binding(13)
+var t_ies: texture_2d<f32>;
+
+@group(0)
+@binding(14)
+var<storage, read> b_light_nodes: array<LightBvhNode>;
+
+@group(0)
+@binding(15)
+var<storage, read> b_bvh_nodes: array<BvhNode>;
+
+@group(0)
+@binding(16)
+var<uniform> u_environment: Environment;
+
 struct Ray {
     origin: vec3<f32>,
     direction: vec3<f32>,
@@ -87,8 +224,16 @@ struct HitPayload {
     hit_distance: f32,
     position: vec3<f32>,
     tex_coord: vec2<f32>,
+    tex_coord_1: vec2<f32>,
+    // both normals face the side of the surface the ray came from
     normal: vec3<f32>,
+    geometric_normal: vec3<f32>,
+    front_face: bool,
+    // emissive primitives aren't covered by next event estimation
+    is_primitive: bool,
+    color: vec3<f32>,
     material_index: u32,
+    light_index: u32,
 }
 
 fn per_pixel(coord: vec2<u32>) {
@@ -96,56 +241,174 @@ fn per_pixel(coord: vec2<u32>) {
 
     var acc_color: vec3<f32> = vec3<f32>(0.0);
     for (var i = 0u; i < u_settings.samples_per_render; i++) {
-        var ray: Ray;
-        ray.origin = u_camera.position;
-        ray.direction = get_ray_direction(coord);
+        var ray = get_camera_ray(coord);
+
+        if u_settings.integrator == INTEGRATOR_PREVIEW {
+            acc_color += preview(ray, furnace_test);
+            continue;
+        }
 
         var light = vec3<f32>(0.0);
         var contribution = vec3<f32>(1.0);
+        var absorption = vec3<f32>(0.0);
+        var specular_bounce = false;
 
         for (var j = 0u; j < u_settings.max_ray_depth; j++) {
-            var payload = trace_ray(ray);
+            var payload = trace_ray(ray, j == 0u);
             if payload.hit_distance < 0.0 {
                 break;
             }
 
+            contribution *= exp(-absorption * payload.hit_distance);
+
+            if payload.light_index != NO_LIGHT {
+                // area lights reached through lobes covered by next event estimation were already counted
+                if j == 0u || specular_bounce {
+                    light += contribution * area_light_radiance(b_lights[payload.light_index], ray.direction, payload.normal);
+                }
+                contribution = vec3<f32>(0.0);
+                break;
+            }
+
             var material = b_materials[payload.material_index];
+            if material.base_color_texture != NO_TEXTURE {
+                material.albedo *= sample_material_texture(material.base_color_texture, payload_tex_coord(payload, material.base_color_tex_coord)).rgb;
+            }
+            material.albedo *= payload.color;
             material.albedo = mix(material.albedo, vec3<f32>(1.0), furnace_test);
 
-            light += contribution * material.emission;
-            contribution *= material.albedo;
+            // emissive triangles are sampled by next event estimation as well
+            if j == 0u || specular_bounce || payload.is_primitive {
+                light += contribution * material.emission;
+            }
+
+            let front_face = payload.front_face;
+            let normal = payload.normal;
+            let geometric_normal = payload.geometric_normal;
+
+            light += contribution * sample_lights(material, payload.position, normal, geometric_normal, front_face, ray.direction);
+
+            var transmitted: bool;
+            if !sample_material(material, normal, front_face, &ray.direction, &contribution, &transmitted, &specular_bounce) || !same_side(ray.direction, normal, geometric_normal) {
+                contribution = vec3<f32>(0.0);
+                break;
+            }
+            ray.origin = offset_ray(payload.position, geometric_normal, ray.direction);
 
-            ray.origin = payload.position;
-            ray.direction = normalize(rand_unit_sphere() + payload.normal);
+            if transmitted && material.thin_walled == 0u {
+                absorption = select(vec3<f32>(0.0), material.absorption, front_face);
+            }
         }
 
         var environment_color = sample_environment(ray.direction) * u_settings.environment_brightness;
-        light += contribution * vec3<f32>(0.1, 0.2, 0.5); // * mix(environment_color, vec3<f32>(1.0), furnace_test);
+        light += contribution * mix(environment_color, vec3<f32>(1.0), furnace_test);
 
         acc_color += light;
     }
 
-    acc_color += textureLoad(t_acc_input, coord, 0).rgb;
+    // the accumulation holds the running mean, a running sum would outgrow the precision and the
+    // range of half floats
+    let samples = f32(u_settings.samples_per_render);
+    let weight = samples / f32(u_per_render.num_samples + u_settings.samples_per_render);
+    let mean_color = mix(textureLoad(t_acc_input, coord, 0).rgb, acc_color / samples, weight);
 
-    var output_color = acc_color / f32(u_per_render.num_samples + u_settings.samples_per_render);
-    output_color = aces_approx(output_color);
+    let output_color = aces_approx(mean_color * u_settings.exposure);
 
-    textureStore(t_acc_output, coord, vec4<f32>(acc_color, 1.0));
+    textureStore(t_acc_output, coord, vec4<f32>(mean_color, 1.0));
     textureStore(t_output, coord, vec4<f32>(output_color, 1.0));
 }
 
-fn trace_ray(ray: Ray) -> HitPayload {
-    var hit_distance = INF;
-    var triangle_index: u32;
-    var hit_uv: vec2<f32>;
+// one bounce approximation: direct light from a single light sample and image based lighting
+// from the environment mips, with the level picked by roughness
+fn preview(ray: Ray, furnace_test: f32) -> vec3<f32> {
+    let payload = trace_ray(ray, true);
+    if payload.hit_distance < 0.0 {
+        return mix(sample_environment(ray.direction) * u_settings.environment_brightness, vec3<f32>(1.0), furnace_test);
+    }
+
+    if payload.light_index != NO_LIGHT {
+        return area_light_radiance(b_lights[payload.light_index], ray.direction, payload.normal);
+    }
+
+    var material = b_materials[payload.material_index];
+    if material.base_color_texture != NO_TEXTURE {
+        material.albedo *= sample_material_texture(material.base_color_texture, payload_tex_coord(payload, material.base_color_tex_coord)).rgb;
+    }
+    material.albedo *= payload.color;
+    material.albedo = mix(material.albedo, vec3<f32>(1.0), furnace_test);
+
+    let front_face = payload.front_face;
+    let normal = payload.normal;
+    let geometric_normal = payload.geometric_normal;
+
+    var light = material.emission + sample_lights(material, payload.position, normal, geometric_normal, front_face, ray.direction);
+
+    let view = -ray.direction;
+    let cos_v = max(dot(normal, view), 0.0);
+    let max_lod = f32(textureNumLevels(t_environment) - 1u);
+    let reflected = reflect(ray.direction, normal);
+    let glossy = preview_environment(reflected, material.roughness * max_lod, furnace_test);
+    let diffuse = mix(eval_sh(u_environment.irradiance_sh, normal) * u_settings.environment_brightness, vec3<f32>(1.0), furnace_test);
+
+    let f0_ior = pow((material.ior - 1.0) / (material.ior + 1.0), 2.0);
+    let f0 = min(f0_ior * material.specular_color, vec3<f32>(1.0)) * material.specular;
+    let dielectric_specular = env_brdf_approx(f0, material.roughness, cos_v);
+    let metal_specular = env_brdf_approx(material.albedo, material.roughness, cos_v);
+
+    var dielectric = dielectric_specular * glossy + material.albedo * (1.0 - dielectric_specular) * diffuse;
+    if material.transmission > 0.0 {
+        let eta = select(material.ior, 1.0 / material.ior, front_face);
+        let fresnel = fresnel_dielectric(cos_v, eta);
+        let refracted = refract(ray.direction, normal, eta);
+        let transmitted = select(reflected, refracted, dot(refracted, refracted) > 0.0);
+        let transmission = fresnel * glossy + (1.0 - fresnel) * material.albedo * preview_environment(transmitted, material.roughness * max_lod, furnace_test);
+        dielectric = mix(dielectric, transmission, material.transmission);
+    }
+
+    light += mix(dielectric, metal_specular * glossy, material.metallic);
+    return light;
+}
+
+// irradiance over pi of the environment, projected to spherical harmonics on the cpu
+fn eval_sh(sh: array<vec4<f32>, 9>, n: vec3<f32>) -> vec3<f32> {
+    let irradiance = sh[0].rgb * 0.282095
+        + sh[1].rgb * 0.488603 * n.y
+        + sh[2].rgb * 0.488603 * n.z
+        + sh[3].rgb * 0.488603 * n.x
+        + sh[4].rgb * 1.092548 * n.x * n.y
+        + sh[5].rgb * 1.092548 * n.y * n.z
+        + sh[6].rgb * 0.315392 * (3.0 * n.z * n.z - 1.0)
+        + sh[7].rgb * 1.092548 * n.x * n.z
+        + sh[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+    // the truncated series rings below zero opposite to bright lights
+    return max(irradiance, vec3<f32>(0.0));
+}
+
+fn preview_environment(dir: vec3<f32>, lod: f32, furnace_test: f32) -> vec3<f32> {
+    return mix(sample_environment_lod(dir, lod) * u_settings.environment_brightness, vec3<f32>(1.0), furnace_test);
+}
 
-    for (var i: u32 = 0u; i < arrayLength(&b_triangles); i++) {
-        var t: f32;
-        var uv: vec2<f32>;
-        if ray_triangle_intersection(ray, i, &t, &uv) && t > EPSILON && t < hit_distance {
+// primary rays don't see area lights that are hidden from the camera
+fn trace_ray(ray: Ray, primary: bool) -> HitPayload {
+    var hit: SceneHit;
+    intersect_scene(ray, INF, false, &hit);
+    var hit_distance = hit.distance;
+
+    var light_index = NO_LIGHT;
+    for (var i: u32 = 0u; i < arrayLength(&b_lights); i++) {
+        let light = b_lights[i];
+        // emissive triangles come last and are intersected as geometry
+        if light.kind == LIGHT_KIND_TRIANGLE {
+            break;
+        }
+        if primary && (light.flags & LIGHT_FLAG_VISIBLE) == 0u {
+            continue;
+        }
+
+        let t = ray_area_light_intersection(ray, light);
+        if t > 0.0 && t < hit_distance {
             hit_distance = t;
-            triangle_index = i;
-            hit_uv = uv;
+            light_index = i;
         }
     }
 
@@ -153,15 +416,199 @@ fn trace_ray(ray: Ray) -> HitPayload {
         return miss(ray);
     }
 
-    return closest_hit(ray, hit_distance, triangle_index, hit_uv);
+    if light_index != NO_LIGHT {
+        return area_light_hit(ray, hit_distance, light_index);
+    }
+
+    return closest_hit(ray, hit);
 }
 
-fn closest_hit(ray: Ray, hit_distance: f32, triangle_index: u32, uv: vec2<f32>) -> HitPayload {
-    var payload: HitPayload;
+struct SceneHit {
+    distance: f32,
+    instance_index: u32,
+    triangle_index: u32,
+    uv: vec2<f32>,
+}
 
-    payload.hit_distance = hit_distance;
+// walks the top level bvh over the instances and the bottom level bvh of every instance it
+// reaches, `any_hit` stops at the first hit closer than `max_distance` for shadow rays
+fn intersect_scene(ray: Ray, max_distance: f32, any_hit: bool, hit: ptr<function, SceneHit>) -> bool {
+    (*hit).distance = max_distance;
+    var found = false;
+    let inv_direction = safe_inverse(ray.direction);
+
+    var stack: array<u32, BVH_STACK_SIZE>;
+    stack[0] = 0u;
+    var stack_size = 1u;
+    while stack_size > 0u {
+        stack_size--;
+        let node = b_bvh_nodes[stack[stack_size]];
+        if ray_aabb_intersection(ray.origin, inv_direction, node.min, node.max) >= (*hit).distance {
+            continue;
+        }
+
+        if node.count == 0u {
+            if stack_size + 2u <= BVH_STACK_SIZE {
+                stack[stack_size] = node.first;
+                stack[stack_size + 1u] = node.first + 1u;
+                stack_size += 2u;
+            }
+            continue;
+        }
 
+        for (var i = node.first; i < node.first + node.count; i++) {
+            if intersect_instance(ray, i, any_hit, hit) {
+                found = true;
+                if any_hit {
+                    return true;
+                }
+            }
+        }
+    }
+    return found;
+}
+
+// the ray is moved into object space without normalizing its direction, so hit distances stay
+// in world units
+fn intersect_instance(world_ray: Ray, instance_index: u32, any_hit: bool, hit: ptr<function, SceneHit>) -> bool {
+    let instance = b_instances[instance_index];
+    var ray: Ray;
+    ray.origin = (instance.inverse_transform * vec4<f32>(world_ray.origin, 1.0)).xyz;
+    ray.direction = (instance.inverse_transform * vec4<f32>(world_ray.direction, 0.0)).xyz;
+
+    if instance.kind != INSTANCE_KIND_MESH {
+        let t = ray_primitive_intersection(ray, instance.kind);
+        if t > 0.0 && t < (*hit).distance {
+            (*hit).distance = t;
+            (*hit).instance_index = instance_index;
+            return true;
+        }
+        return false;
+    }
+
+    let inv_direction = safe_inverse(ray.direction);
+    var found = false;
+
+    var stack: array<u32, BVH_STACK_SIZE>;
+    stack[0] = instance.blas_root;
+    var stack_size = 1u;
+    while stack_size > 0u {
+        stack_size--;
+        let node = b_bvh_nodes[stack[stack_size]];
+        if ray_aabb_intersection(ray.origin, inv_direction, node.min, node.max) >= (*hit).distance {
+            continue;
+        }
+
+        if node.count == 0u {
+            if stack_size + 2u <= BVH_STACK_SIZE {
+                stack[stack_size] = node.first;
+                stack[stack_size + 1u] = node.first + 1u;
+                stack_size += 2u;
+            }
+            continue;
+        }
+
+        for (var i = node.first; i < node.first + node.count; i++) {
+            var t: f32;
+            var uv: vec2<f32>;
+            if ray_triangle_intersection(ray, i, &t, &uv) && t > 0.0 && t < (*hit).distance && accept_hit(ray, i, instance_material(instance, i), uv) {
+                (*hit).distance = t;
+                (*hit).instance_index = instance_index;
+                (*hit).triangle_index = i;
+                (*hit).uv = uv;
+                found = true;
+                if any_hit {
+                    return true;
+                }
+            }
+        }
+    }
+    return found;
+}
+
+fn instance_material(instance: Instance, triangle_index: u32) -> u32 {
+    return select(b_triangles[triangle_index].material_index, instance.material_index, instance.material_index != NO_MATERIAL);
+}
+
+// zero components become a large inverse instead of relying on division by zero
+fn safe_inverse(v: vec3<f32>) -> vec3<f32> {
+    return select(1.0 / v, vec3<f32>(INF), abs(v) < vec3<f32>(1.0 / INF));
+}
+
+// returns the distance to the entry point, or INF if the box is missed or behind the ray
+fn ray_aabb_intersection(origin: vec3<f32>, inv_direction: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> f32 {
+    let t0 = (box_min - origin) * inv_direction;
+    let t1 = (box_max - origin) * inv_direction;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let t_near = max(max(t_min.x, t_min.y), t_min.z);
+    let t_far = min(min(t_max.x, t_max.y), t_max.z);
+    if t_near > t_far || t_far < 0.0 {
+        return INF;
+    }
+    return max(t_near, 0.0);
+}
+
+// the back of single-sided surfaces is invisible as with back-face culling in glTF, except for
+// transmissive materials whose back faces are hit from the inside
+fn accept_hit(ray: Ray, triangle_index: u32, material_index: u32, uv: vec2<f32>) -> bool {
+    let material = b_materials[material_index];
+    if material.double_sided == 0u && material.transmission == 0.0 {
+        let triangle = b_triangles[triangle_index];
+        let p0 = b_vertices[triangle.vertex_indices[0]].position;
+        let p1 = b_vertices[triangle.vertex_indices[1]].position;
+        let p2 = b_vertices[triangle.vertex_indices[2]].position;
+        if dot(ray.direction, cross(p1 - p0, p2 - p0)) > 0.0 {
+            return false;
+        }
+    }
+    return alpha_test(triangle_index, material_index, uv);
+}
+
+// decides whether a hit on a masked or blended triangle counts, blending is stochastic
+fn alpha_test(triangle_index: u32, material_index: u32, uv: vec2<f32>) -> bool {
     let triangle = b_triangles[triangle_index];
+    let material = b_materials[material_index];
+    if material.alpha_mode == ALPHA_MODE_OPAQUE {
+        return true;
+    }
+
+    let c0 = vertex_color(triangle.vertex_indices[0]);
+    let c1 = vertex_color(triangle.vertex_indices[1]);
+    let c2 = vertex_color(triangle.vertex_indices[2]);
+    var alpha = material.alpha * ((1.0 - uv.x - uv.y) * c0.a + uv.x * c1.a + uv.y * c2.a);
+    if material.base_color_texture != NO_TEXTURE {
+        let tex_coord_set = material.base_color_tex_coord;
+        let tc0 = vertex_tex_coord(triangle.vertex_indices[0], tex_coord_set);
+        let tc1 = vertex_tex_coord(triangle.vertex_indices[1], tex_coord_set);
+        let tc2 = vertex_tex_coord(triangle.vertex_indices[2], tex_coord_set);
+        let tex_coord = (1.0 - uv.x - uv.y) * tc0 + uv.x * tc1 + uv.y * tc2;
+        alpha *= sample_material_texture(material.base_color_texture, tex_coord).a;
+    }
+
+    if material.alpha_mode == ALPHA_MODE_MASK {
+        return alpha >= material.alpha_cutoff;
+    }
+    return rand(0.0, 1.0) < alpha;
+}
+
+fn trace_shadow_ray(ray: Ray, max_distance: f32) -> bool {
+    var hit: SceneHit;
+    return intersect_scene(ray, max_distance, true, &hit);
+}
+
+fn closest_hit(ray: Ray, hit: SceneHit) -> HitPayload {
+    var payload: HitPayload;
+
+    payload.hit_distance = hit.distance;
+
+    let instance = b_instances[hit.instance_index];
+    if instance.kind != INSTANCE_KIND_MESH {
+        return primitive_hit(ray, hit, instance);
+    }
+
+    let triangle = b_triangles[hit.triangle_index];
+    let uv = hit.uv;
 
     let v0i = triangle.vertex_indices[0];
     let v1i = triangle.vertex_indices[1];
@@ -171,16 +618,190 @@ fn closest_hit(ray: Ray, hit_distance: f32, triangle_index: u32, uv: vec2<f32>)
     var tc1 = b_vertices[v1i].tex_coord;
     var tc2 = b_vertices[v2i].tex_coord;
     let tex_coord = (1.0 - uv.x - uv.y) * tc0 + uv.x * tc1 + uv.y * tc2;
+    tc0 = b_vertices[v0i].tex_coord_1;
+    tc1 = b_vertices[v1i].tex_coord_1;
+    tc2 = b_vertices[v2i].tex_coord_1;
+    let tex_coord_1 = (1.0 - uv.x - uv.y) * tc0 + uv.x * tc1 + uv.y * tc2;
 
     let n0 = b_vertices[v0i].normal;
     let n1 = b_vertices[v1i].normal;
     let n2 = b_vertices[v2i].normal;
-    let normal = normalize((1.0 - uv.x - uv.y) * n0 + uv.x * n1 + uv.y * n2);
-
-    payload.position = ray.origin + ray.direction * hit_distance;
+    let t0 = b_vertices[v0i].tangent;
+    let t1 = b_vertices[v1i].tangent;
+    let t2 = b_vertices[v2i].tangent;
+    let c0 = vertex_color(v0i);
+    let c1 = vertex_color(v1i);
+    let c2 = vertex_color(v2i);
+    let color = (1.0 - uv.x - uv.y) * c0 + uv.x * c1 + uv.y * c2;
+
+    let object_normal = (1.0 - uv.x - uv.y) * n0 + uv.x * n1 + uv.y * n2;
+    // normals go through the inverse transpose to stay perpendicular under non-uniform scaling
+    var normal = normalize((vec4<f32>(object_normal, 0.0) * instance.inverse_transform).xyz);
+
+    let p0 = b_vertices[v0i].position;
+    let p1 = b_vertices[v1i].position;
+    let p2 = b_vertices[v2i].position;
+    var geometric_normal = normalize((vec4<f32>(cross(p1 - p0, p2 - p0), 0.0) * instance.inverse_transform).xyz);
+    // the outside is where the vertex normals point to, whatever the winding
+    geometric_normal = select(geometric_normal, -geometric_normal, dot(geometric_normal, normal) < 0.0);
+    // the barycentric position is on the triangle, unlike origin + t * direction whose error grows
+    // with the distance travelled
+    let object_position = (1.0 - uv.x - uv.y) * p0 + uv.x * p1 + uv.y * p2;
+    payload.position = (instance.transform * vec4<f32>(object_position, 1.0)).xyz;
     payload.tex_coord = tex_coord;
+    payload.tex_coord_1 = tex_coord_1;
+    payload.color = color.rgb;
+    payload.material_index = instance_material(instance, hit.triangle_index);
+    payload.light_index = NO_LIGHT;
+
+    let material = b_materials[payload.material_index];
+    let object_tangent = (1.0 - uv.x - uv.y) * t0 + uv.x * t1 + uv.y * t2;
+    if material.normal_texture != NO_TEXTURE && dot(object_tangent.xyz, object_tangent.xyz) > 0.0 {
+        // tangents follow the surface, mirroring transforms flip the handedness of the frame
+        let transform = mat3x3<f32>(instance.transform[0].xyz, instance.transform[1].xyz, instance.transform[2].xyz);
+        let handedness = sign(object_tangent.w) * select(1.0, -1.0, determinant(transform) < 0.0);
+        let tangent = normalize((instance.transform * vec4<f32>(object_tangent.xyz, 0.0)).xyz);
+        normal = perturb_normal(material, payload, normal, tangent, handedness);
+    }
+
+    payload.front_face = dot(ray.direction, geometric_normal) < 0.0;
+    if !payload.front_face {
+        normal = -normal;
+        geometric_normal = -geometric_normal;
+    }
+    payload.normal = bend_shading_normal(normal, -ray.direction);
+    payload.geometric_normal = geometric_normal;
+
+    return payload;
+}
+
+// the hit point is projected back onto the canonical primitive so that rays leaving it start
+// from an accurate position. primitives ignore alpha, normal maps and single-sidedness
+fn primitive_hit(ray: Ray, hit: SceneHit, instance: Instance) -> HitPayload {
+    var payload: HitPayload;
+
+    payload.hit_distance = hit.distance;
+
+    var position = (instance.inverse_transform * vec4<f32>(ray.origin + ray.direction * hit.distance, 1.0)).xyz;
+    var object_normal = vec3<f32>(0.0, 0.0, 1.0);
+    switch instance.kind {
+        case INSTANCE_KIND_SPHERE: {
+            position = normalize(position);
+            object_normal = position;
+            payload.tex_coord = vec2<f32>(0.5 + atan2(position.x, position.z) / (2.0 * PI), acos(clamp(position.y, -1.0, 1.0)) / PI);
+        }
+        case INSTANCE_KIND_BOX: {
+            // the face is the one of the axis the hit point is furthest along
+            let distance = abs(position);
+            var axis = 2u;
+            if distance.x >= distance.y && distance.x >= distance.z {
+                axis = 0u;
+            } else if distance.y >= distance.z {
+                axis = 1u;
+            }
+            object_normal = vec3<f32>(0.0);
+            object_normal[axis] = select(-1.0, 1.0, position[axis] >= 0.0);
+            position[axis] = object_normal[axis];
+            let face = select(select(position.xy, position.xz, axis == 1u), position.yz, axis == 0u);
+            payload.tex_coord = face * 0.5 + 0.5;
+        }
+        default: {
+            position.z = 0.0;
+            // textures repeat every unit on planes and span the whole disk
+            payload.tex_coord = select(position.xy * 0.5 + 0.5, position.xy, instance.kind == INSTANCE_KIND_PLANE);
+        }
+    }
+
+    var normal = normalize((vec4<f32>(object_normal, 0.0) * instance.inverse_transform).xyz);
+    payload.front_face = dot(ray.direction, normal) < 0.0;
+    normal = select(-normal, normal, payload.front_face);
+
+    payload.position = (instance.transform * vec4<f32>(position, 1.0)).xyz;
+    payload.tex_coord_1 = payload.tex_coord;
     payload.normal = normal;
-    payload.material_index = triangle.material_index;
+    payload.geometric_normal = normal;
+    payload.is_primitive = true;
+    payload.color = vec3<f32>(1.0);
+    payload.material_index = instance.material_index;
+    payload.light_index = NO_LIGHT;
+
+    return payload;
+}
+
+fn perturb_normal(material: Material, payload: HitPayload, normal: vec3<f32>, tangent: vec3<f32>, handedness: f32) -> vec3<f32> {
+    // interpolation and non-uniform scaling leave the tangent slightly off the normal plane
+    let t = normalize(tangent - normal * dot(normal, tangent));
+    let b = cross(normal, t) * handedness;
+
+    var mapped = sample_material_texture(material.normal_texture, payload_tex_coord(payload, material.normal_tex_coord)).xyz * 2.0 - 1.0;
+    mapped = vec3<f32>(mapped.xy * material.normal_scale, mapped.z);
+    let perturbed = t * mapped.x + b * mapped.y + normal * mapped.z;
+    if dot(perturbed, perturbed) <= 0.0 {
+        return normal;
+    }
+    return normalize(perturbed);
+}
+
+// interpolated and normal mapped normals can face away from a viewer that sees the triangle,
+// which turns the hit black. such normals are bent towards the viewer until it sees them
+fn bend_shading_normal(normal: vec3<f32>, view: vec3<f32>) -> vec3<f32> {
+    let cos_view = dot(normal, view);
+    let min_cos = 0.01;
+    if cos_view >= min_cos {
+        return normal;
+    }
+    return normalize(normal + (min_cos - cos_view) * view);
+}
+
+// directions that are above the shading normal but below the triangle (or the other way round)
+// would leak light through the surface
+fn same_side(direction: vec3<f32>, normal: vec3<f32>, geometric_normal: vec3<f32>) -> bool {
+    return dot(direction, normal) * dot(direction, geometric_normal) > 0.0;
+}
+
+// moves the origin of a ray leaving the surface at `position` off the surface, towards the side
+// `direction` points to. the offset is a few ulps scaled by the magnitude of the position, so
+// rays neither hit the surface they start on nor skip nearby geometry (Waechter and Binder, "A
+// Fast and Robust Method for Avoiding Self-Intersection", Ray Tracing Gems)
+fn offset_ray(position: vec3<f32>, geometric_normal: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    let origin = 1.0 / 32.0;
+    let float_scale = 1.0 / 65536.0;
+    let int_scale = 256.0;
+
+    let normal = select(-geometric_normal, geometric_normal, dot(direction, geometric_normal) > 0.0);
+    let int_offset = vec3<i32>(int_scale * normal);
+    let int_position = bitcast<vec3<f32>>(bitcast<vec3<i32>>(position) + select(int_offset, -int_offset, position < vec3<f32>(0.0)));
+    return select(int_position, position + float_scale * normal, abs(position) < vec3<f32>(origin));
+}
+
+fn vertex_tex_coord(vertex_index: u32, tex_coord_set: u32) -> vec2<f32> {
+    let vertex = b_vertices[vertex_index];
+    return select(vertex.tex_coord, vertex.tex_coord_1, tex_coord_set == 1u);
+}
+
+fn payload_tex_coord(payload: HitPayload, tex_coord_set: u32) -> vec2<f32> {
+    return select(payload.tex_coord, payload.tex_coord_1, tex_coord_set == 1u);
+}
+
+fn vertex_color(vertex_index: u32) -> vec4<f32> {
+    let vertex = b_vertices[vertex_index];
+    return vec4<f32>(unpack2x16float(vertex.color_rg), unpack2x16float(vertex.color_ba));
+}
+
+fn area_light_hit(ray: Ray, hit_distance: f32, light_index: u32) -> HitPayload {
+    var payload: HitPayload;
+
+    let light = b_lights[light_index];
+
+    payload.hit_distance = hit_distance;
+    payload.position = ray.origin + ray.direction * hit_distance;
+    payload.normal = light.direction;
+    if light.kind == LIGHT_KIND_SPHERE {
+        payload.normal = normalize(payload.position - light.position);
+    }
+    payload.geometric_normal = payload.normal;
+    payload.front_face = dot(ray.direction, payload.normal) < 0.0;
+    payload.light_index = light_index;
 
     return payload;
 }
@@ -228,14 +849,116 @@ fn rand_unit_sphere() -> vec3<f32> {
     return normalize(rand_vec3(-1.0, 1.0));
 }
 
-fn get_ray_direction(coord: vec2<u32>) -> vec3<f32> {
+fn get_camera_ray(coord: vec2<u32>) -> Ray {
     var size = textureDimensions(t_output);
     var coord_unit = (vec2<f32>(coord) + rand_vec2(-0.5, 0.5)) / vec2<f32>(size);
     var final_coord = coord_unit * 2.0 - 1.0;
     final_coord.y = -final_coord.y; // flip the y coordinate
-    var target_position = u_camera.inverse_projection * vec4<f32>(final_coord, 1.0, 1.0);
-    var direction = (u_camera.inverse_view * vec4<f32>(normalize(target_position.xyz / target_position.w), 0.0)).xyz;
-    return direction;
+
+    var ray: Ray;
+    if u_camera.orthographic != 0u {
+        // parallel rays starting on the near plane
+        var near_position = u_camera.inverse_projection * vec4<f32>(final_coord, 0.0, 1.0);
+        ray.origin = (u_camera.inverse_view * vec4<f32>(near_position.xyz / near_position.w, 1.0)).xyz;
+        ray.direction = normalize((u_camera.inverse_view * vec4<f32>(0.0, 0.0, -1.0, 0.0)).xyz);
+    } else {
+        var target_position = u_camera.inverse_projection * vec4<f32>(final_coord, 1.0, 1.0);
+        ray.origin = u_camera.position;
+        ray.direction = (u_camera.inverse_view * vec4<f32>(normalize(target_position.xyz / target_position.w), 0.0)).xyz;
+    }
+    return ray;
+}
+
+// returns the distance to the area light or a negative value if the ray misses it
+fn ray_area_light_intersection(ray: Ray, light: Light) -> f32 {
+    switch light.kind {
+        case LIGHT_KIND_RECT, LIGHT_KIND_DISK: {
+            let denom = dot(ray.direction, light.direction);
+            if abs(denom) < 0.00000001 {
+                return -1.0;
+            }
+
+            let t = dot(light.position - ray.origin, light.direction) / denom;
+            let local = ray.origin + ray.direction * t - light.position;
+            if light.kind == LIGHT_KIND_DISK {
+                if dot(local, local) > light.radius * light.radius {
+                    return -1.0;
+                }
+            } else if abs(dot(local, light.edge_u)) > 0.5 * dot(light.edge_u, light.edge_u) || abs(dot(local, light.edge_v)) > 0.5 * dot(light.edge_v, light.edge_v) {
+                return -1.0;
+            }
+            return t;
+        }
+        case LIGHT_KIND_SPHERE: {
+            let oc = ray.origin - light.position;
+            let b = dot(oc, ray.direction);
+            let c = dot(oc, oc) - light.radius * light.radius;
+            let discriminant = b * b - c;
+            if discriminant < 0.0 {
+                return -1.0;
+            }
+
+            let sqrt_discriminant = sqrt(discriminant);
+            let t = -b - sqrt_discriminant;
+            if t > 0.0 {
+                return t;
+            }
+            return -b + sqrt_discriminant;
+        }
+        default: {
+            return -1.0;
+        }
+    }
+}
+
+// returns the distance to the canonical primitive (see `primitive_hit`) or a negative value if
+// the ray misses it, the direction doesn't have to be normalized
+fn ray_primitive_intersection(ray: Ray, kind: u32) -> f32 {
+    switch kind {
+        case INSTANCE_KIND_SPHERE: {
+            let a = dot(ray.direction, ray.direction);
+            let b = dot(ray.origin, ray.direction);
+            let c = dot(ray.origin, ray.origin) - 1.0;
+            let discriminant = b * b - a * c;
+            if discriminant < 0.0 {
+                return -1.0;
+            }
+
+            let sqrt_discriminant = sqrt(discriminant);
+            let t = (-b - sqrt_discriminant) / a;
+            if t > 0.0 {
+                return t;
+            }
+            return (-b + sqrt_discriminant) / a;
+        }
+        case INSTANCE_KIND_BOX: {
+            let inv_direction = safe_inverse(ray.direction);
+            let t0 = (vec3<f32>(-1.0) - ray.origin) * inv_direction;
+            let t1 = (vec3<f32>(1.0) - ray.origin) * inv_direction;
+            let t_min = min(t0, t1);
+            let t_max = max(t0, t1);
+            let t_near = max(max(t_min.x, t_min.y), t_min.z);
+            let t_far = min(min(t_max.x, t_max.y), t_max.z);
+            if t_near > t_far {
+                return -1.0;
+            }
+            // rays starting inside leave through the far side
+            return select(t_far, t_near, t_near > 0.0);
+        }
+        default: {
+            // planes and disks lie in z = 0
+            if ray.direction.z == 0.0 {
+                return -1.0;
+            }
+
+            let t = -ray.origin.z / ray.direction.z;
+            let local = ray.origin.xy + ray.direction.xy * t;
+            if kind == INSTANCE_KIND_DISK && dot(local, local) > 1.0 {
+                return -1.0;
+            }
+            return t;
+        }
+    }
 }
 
 fn ray_triangle_intersection(ray: Ray, triangle_index: u32, t: ptr<function, f32>, uv: ptr<function, vec2<f32>>) -> bool {
@@ -280,26 +1003,724 @@ fn ray_triangle_intersection(ray: Ray, triangle_index: u32, t: ptr<function, f32
     return true;
 }
 
-fn sample_texture(tex: texture_2d<f32>, uv: vec2<f32>) -> vec4<f32> {
-    let size = textureDimensions(tex);
+// next event estimation for the analytic lights, picks one light uniformly and returns the
+// unoccluded radiance it reflects towards the incoming ray
+fn sample_lights(material: Material, position: vec3<f32>, normal: vec3<f32>, geometric_normal: vec3<f32>, front_face: bool, incoming: vec3<f32>) -> vec3<f32> {
+    var pmf: f32;
+    let light_index = select_light(position, normal, material.transmission > 0.0, &pmf);
+    if light_index == NO_LIGHT {
+        return vec3<f32>(0.0);
+    }
+
+    let light = b_lights[light_index];
+    if all(light.intensity == vec3<f32>(0.0)) {
+        return vec3<f32>(0.0);
+    }
+
+    var direction: vec3<f32>;
+    var distance: f32;
+    var irradiance: vec3<f32>;
+    switch light.kind {
+        case LIGHT_KIND_DIRECTIONAL: {
+            direction = sample_cone(-light.direction, light.cos_outer_angle);
+            distance = INF;
+            irradiance = light.intensity;
+        }
+        case LIGHT_KIND_RECT, LIGHT_KIND_DISK, LIGHT_KIND_SPHERE, LIGHT_KIND_TRIANGLE: {
+            // the light radiance divided by the solid angle pdf of the sampled direction
+            var inv_pdf: f32;
+            switch light.kind {
+                case LIGHT_KIND_RECT: {
+                    inv_pdf = sample_rect_light(light, position, &direction, &distance);
+                }
+                case LIGHT_KIND_DISK: {
+                    inv_pdf = sample_disk_light(light, position, &direction, &distance);
+                }
+                case LIGHT_KIND_TRIANGLE: {
+                    inv_pdf = sample_triangle_light(light, position, &direction, &distance);
+                }
+                default: {
+                    inv_pdf = sample_sphere_light(light, position, &direction, &distance);
+                }
+            }
+            if inv_pdf <= 0.0 {
+                return vec3<f32>(0.0);
+            }
+
+            var light_normal = light.direction;
+            if light.kind == LIGHT_KIND_SPHERE {
+                light_normal = normalize(position + direction * distance - light.position);
+            }
+            irradiance = area_light_radiance(light, direction, light_normal) * inv_pdf;
+        }
+        default: {
+            let to_light = light.position - position;
+            distance = length(to_light);
+            direction = to_light / distance;
+            irradiance = light.intensity / (distance * distance);
+
+            if light.kind == LIGHT_KIND_SPOT {
+                let cos_angle = dot(-direction, light.direction);
+                let t = clamp((cos_angle - light.cos_outer_angle) / max(light.cos_inner_angle - light.cos_outer_angle, 0.0001), 0.0, 1.0);
+                irradiance *= t * t;
+            }
+
+            if light.ies_profile != NO_IES_PROFILE {
+                irradiance *= ies_intensity(light, -direction);
+            }
+        }
+    }
+
+    if !same_side(direction, normal, geometric_normal) {
+        return vec3<f32>(0.0);
+    }
+
+    let bsdf = eval_material(material, normal, front_face, incoming, direction);
+    if all(bsdf == vec3<f32>(0.0)) {
+        return vec3<f32>(0.0);
+    }
+
+    var shadow_ray: Ray;
+    shadow_ray.origin = offset_ray(position, geometric_normal, direction);
+    shadow_ray.direction = direction;
+    // relative offset so the shadow ray doesn't hit the sampled emissive triangle itself
+    if trace_shadow_ray(shadow_ray, min(distance - EPSILON, distance * 0.9999)) {
+        return vec3<f32>(0.0);
+    }
+
+    return bsdf * irradiance / pmf;
+}
+
+// picks a light for next event estimation, directional lights (first in the lights buffer) are
+// picked uniformly and the others by traversing the light bvh, as in pbrt-v4
+fn select_light(position: vec3<f32>, normal: vec3<f32>, transmissive: bool, pmf: ptr<function, f32>) -> u32 {
+    var num_infinite = 0u;
+    while num_infinite < arrayLength(&b_lights) && b_lights[num_infinite].kind == LIGHT_KIND_DIRECTIONAL {
+        num_infinite++;
+    }
+
+    let has_bvh = b_light_nodes[0].power > 0.0;
+    if num_infinite == 0u && !has_bvh {
+        return NO_LIGHT;
+    }
+
+    let infinite_probability = f32(num_infinite) / f32(num_infinite + select(0u, 1u, has_bvh));
+    let u = rand(0.0, 1.0);
+    if u < infinite_probability {
+        *pmf = infinite_probability / f32(num_infinite);
+        return min(u32(u / infinite_probability * f32(num_infinite)), num_infinite - 1u);
+    }
+
+    // surfaces that only reflect don't receive light from below
+    var receiver_normal = normal;
+    if transmissive {
+        receiver_normal = vec3<f32>(0.0);
+    }
+
+    var node_index = 0u;
+    *pmf = 1.0 - infinite_probability;
+    if light_node_importance(b_light_nodes[0], position, receiver_normal) <= 0.0 {
+        return NO_LIGHT;
+    }
+
+    loop {
+        let node = b_light_nodes[node_index];
+        if (node.flags & LIGHT_NODE_FLAG_LEAF) != 0u {
+            return node.index;
+        }
+
+        let importance0 = light_node_importance(b_light_nodes[node_index + 1u], position, receiver_normal);
+        let importance1 = light_node_importance(b_light_nodes[node.index], position, receiver_normal);
+        if importance0 <= 0.0 && importance1 <= 0.0 {
+            return NO_LIGHT;
+        }
+
+        let probability0 = importance0 / (importance0 + importance1);
+        if rand(0.0, 1.0) < probability0 {
+            node_index += 1u;
+            *pmf *= probability0;
+        } else {
+            node_index = node.index;
+            *pmf *= 1.0 - probability0;
+        }
+    }
+
+    return NO_LIGHT;
+}
+
+// conservative estimate of the light a node contributes at a point, from its power, distance
+// and the bounds of its emission directions, a zero normal ignores the receiver orientation
+fn light_node_importance(node: LightBvhNode, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let center = 0.5 * (node.bounds_min + node.bounds_max);
+    let diagonal = length(node.bounds_max - node.bounds_min);
+    let to_position = position - center;
+    let distance2 = max(dot(to_position, to_position), 0.5 * diagonal);
+    let incident = select(vec3<f32>(0.0, 0.0, 1.0), normalize(to_position), dot(to_position, to_position) > 0.0);
+
+    var cos_theta_w = dot(node.direction, incident);
+    if (node.flags & LIGHT_NODE_FLAG_TWO_SIDED) != 0u {
+        cos_theta_w = abs(cos_theta_w);
+    }
+    let sin_theta_w = sqrt(max(1.0 - cos_theta_w * cos_theta_w, 0.0));
+
+    // cone of directions from the position to the bounds
+    let radius2 = 0.25 * diagonal * diagonal;
+    var cos_theta_b = -1.0;
+    if dot(to_position, to_position) > radius2 {
+        cos_theta_b = sqrt(max(1.0 - radius2 / dot(to_position, to_position), 0.0));
+    }
+    let sin_theta_b = sqrt(max(1.0 - cos_theta_b * cos_theta_b, 0.0));
+
+    let sin_theta_o = sqrt(max(1.0 - node.cos_theta_o * node.cos_theta_o, 0.0));
+    let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, node.cos_theta_o);
+    let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, node.cos_theta_o);
+    let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+    if cos_theta_p <= node.cos_theta_e {
+        return 0.0;
+    }
+
+    var importance = node.power * cos_theta_p / distance2;
+
+    if any(normal != vec3<f32>(0.0)) {
+        let cos_theta_i = -dot(incident, normal);
+        let sin_theta_i = sqrt(max(1.0 - cos_theta_i * cos_theta_i, 0.0));
+        importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+    }
+
+    return max(importance, 0.0);
+}
+
+// cosine of max(theta_a - theta_b, 0)
+fn cos_sub_clamped(sin_theta_a: f32, cos_theta_a: f32, sin_theta_b: f32, cos_theta_b: f32) -> f32 {
+    if cos_theta_a > cos_theta_b {
+        return 1.0;
+    }
+    return cos_theta_a * cos_theta_b + sin_theta_a * sin_theta_b;
+}
+
+// sine of max(theta_a - theta_b, 0)
+fn sin_sub_clamped(sin_theta_a: f32, cos_theta_a: f32, sin_theta_b: f32, cos_theta_b: f32) -> f32 {
+    if cos_theta_a > cos_theta_b {
+        return 0.0;
+    }
+    return sin_theta_a * cos_theta_b - cos_theta_a * sin_theta_b;
+}
+
+// normalized intensity of the light's ies profile in the given direction (pointing away from
+// the light), the profiles are stacked vertically in the ies texture
+fn ies_intensity(light: Light, direction: vec3<f32>) -> f32 {
+    let vertical_angle = acos(clamp(dot(direction, light.direction), -1.0, 1.0));
+    var horizontal_angle = atan2(dot(direction, light.edge_v), dot(direction, light.edge_u));
+    if horizontal_angle < 0.0 {
+        horizontal_angle += 2.0 * PI;
+    }
+
+    let size = textureDimensions(t_ies);
+    let coord = vec2<f32>(horizontal_angle / (2.0 * PI) * f32(size.x), vertical_angle / PI * f32(IES_TABLE_HEIGHT - 1u));
+    let base = vec2<u32>(floor(coord));
+    let frac = coord - floor(coord);
+
+    let x0 = base.x % size.x;
+    let x1 = (base.x + 1u) % size.x;
+    let y0 = light.ies_profile * IES_TABLE_HEIGHT + min(base.y, IES_TABLE_HEIGHT - 1u);
+    let y1 = light.ies_profile * IES_TABLE_HEIGHT + min(base.y + 1u, IES_TABLE_HEIGHT - 1u);
+
+    let s0 = textureLoad(t_ies, vec2(x0, y0), 0).r;
+    let s1 = textureLoad(t_ies, vec2(x0, y1), 0).r;
+    let s2 = textureLoad(t_ies, vec2(x1, y0), 0).r;
+    let s3 = textureLoad(t_ies, vec2(x1, y1), 0).r;
+
+    return s0 * (1.0 - frac.x) * (1.0 - frac.y) + s1 * (1.0 - frac.x) * frac.y + s2 * frac.x * (1.0 - frac.y) + s3 * frac.x * frac.y;
+}
+
+// radiance leaving an area light towards a ray travelling in `direction`
+fn area_light_radiance(light: Light, direction: vec3<f32>, light_normal: vec3<f32>) -> vec3<f32> {
+    if dot(direction, light_normal) < 0.0 || (light.flags & LIGHT_FLAG_TWO_SIDED) != 0u {
+        return light.intensity;
+    }
+    return vec3<f32>(0.0);
+}
+
+// solid angle sampling of a rectangle, from "An Area-Preserving Parametrization for Spherical
+// Rectangles" (Urena et al.), returns the solid angle subtended by the rectangle
+fn sample_rect_light(light: Light, position: vec3<f32>, direction: ptr<function, vec3<f32>>, distance: ptr<function, f32>) -> f32 {
+    let length_u = length(light.edge_u);
+    let length_v = length(light.edge_v);
+    let x = light.edge_u / length_u;
+    let y = light.edge_v / length_v;
+    var z = cross(x, y);
+
+    let corner = light.position - 0.5 * light.edge_u - 0.5 * light.edge_v;
+    let d = corner - position;
+    let x0 = dot(d, x);
+    let y0 = dot(d, y);
+    var z0 = dot(d, z);
+    if z0 > 0.0 {
+        z0 = -z0;
+        z = -z;
+    }
+    if z0 > -EPSILON {
+        return 0.0;
+    }
+
+    let x1 = x0 + length_u;
+    let y1 = y0 + length_v;
+
+    let v00 = vec3<f32>(x0, y0, z0);
+    let v01 = vec3<f32>(x0, y1, z0);
+    let v10 = vec3<f32>(x1, y0, z0);
+    let v11 = vec3<f32>(x1, y1, z0);
+
+    let n0 = normalize(cross(v00, v10));
+    let n1 = normalize(cross(v10, v11));
+    let n2 = normalize(cross(v11, v01));
+    let n3 = normalize(cross(v01, v00));
+
+    let g0 = acos(clamp(-dot(n0, n1), -1.0, 1.0));
+    let g1 = acos(clamp(-dot(n1, n2), -1.0, 1.0));
+    let g2 = acos(clamp(-dot(n2, n3), -1.0, 1.0));
+    let g3 = acos(clamp(-dot(n3, n0), -1.0, 1.0));
+
+    let b0 = n0.z;
+    let b1 = n2.z;
+    let k = 2.0 * PI - g2 - g3;
+    let solid_angle = g0 + g1 - k;
+    if solid_angle <= 0.000001 {
+        return 0.0;
+    }
+
+    let u = rand_vec2(0.0, 1.0);
+
+    let au = u.x * solid_angle + k;
+    let fu = (cos(au) * b0 - b1) / sin(au);
+    var cu = select(-1.0, 1.0, fu > 0.0) / sqrt(fu * fu + b0 * b0);
+    cu = clamp(cu, -1.0, 1.0);
+    var xu = -(cu * z0) / sqrt(max(1.0 - cu * cu, 0.000001));
+    xu = clamp(xu, x0, x1);
+
+    let dist = sqrt(xu * xu + z0 * z0);
+    let h0 = y0 / sqrt(dist * dist + y0 * y0);
+    let h1 = y1 / sqrt(dist * dist + y1 * y1);
+    let hv = h0 + u.y * (h1 - h0);
+    let hv2 = hv * hv;
+    var yv = y1;
+    if hv2 < 1.0 - 0.000001 {
+        yv = (hv * dist) / sqrt(1.0 - hv2);
+    }
+
+    let to_point = xu * x + yv * y + z0 * z;
+    *distance = length(to_point);
+    *direction = to_point / *distance;
+
+    return solid_angle;
+}
+
+// uniform area sampling of a disk, returns the inverse of the solid angle pdf
+fn sample_disk_light(light: Light, position: vec3<f32>, direction: ptr<function, vec3<f32>>, distance: ptr<function, f32>) -> f32 {
+    let u = rand_vec2(0.0, 1.0);
+    let r = light.radius * sqrt(u.x);
+    let phi = 2.0 * PI * u.y;
+    let point = light.position + to_world(vec3<f32>(r * cos(phi), r * sin(phi), 0.0), light.direction);
+
+    let to_point = point - position;
+    *distance = length(to_point);
+    *direction = to_point / *distance;
+
+    let cos_light = abs(dot(*direction, light.direction));
+    if cos_light < 0.000001 {
+        return 0.0;
+    }
+
+    return PI * light.radius * light.radius * cos_light / (*distance * *distance);
+}
+
+// uniformly samples the area of an emissive triangle, returns the area times the cosine at the
+// light over the squared distance
+fn sample_triangle_light(light: Light, position: vec3<f32>, direction: ptr<function, vec3<f32>>, distance: ptr<function, f32>) -> f32 {
+    let u = rand_vec2(0.0, 1.0);
+    let su = sqrt(u.x);
+    let point = light.position + light.edge_u * su * (1.0 - u.y) + light.edge_v * su * u.y;
+
+    let to_point = point - position;
+    *distance = length(to_point);
+    *direction = to_point / *distance;
+
+    let cos_light = abs(dot(*direction, light.direction));
+    if cos_light < 0.000001 {
+        return 0.0;
+    }
+
+    let area = 0.5 * length(cross(light.edge_u, light.edge_v));
+    return area * cos_light / (*distance * *distance);
+}
+
+// uniformly samples the cone of directions subtended by a sphere, returns its solid angle
+fn sample_sphere_light(light: Light, position: vec3<f32>, direction: ptr<function, vec3<f32>>, distance: ptr<function, f32>) -> f32 {
+    let to_center = light.position - position;
+    let distance2 = dot(to_center, to_center);
+    let radius2 = light.radius * light.radius;
+    if distance2 <= radius2 {
+        return 0.0;
+    }
+
+    let cos_max_angle = sqrt(1.0 - radius2 / distance2);
+    *direction = sample_cone(to_center / sqrt(distance2), cos_max_angle);
+
+    let b = dot(*direction, to_center);
+    *distance = b - sqrt(max(b * b - distance2 + radius2, 0.0));
+
+    return 2.0 * PI * (1.0 - cos_max_angle);
+}
+
+// evaluates the layered material times the cosine term, matching the lobes and layer
+// probabilities of sample_material (the specular transmission lobe isn't evaluated)
+fn eval_material(material: Material, normal: vec3<f32>, front_face: bool, incoming: vec3<f32>, outgoing: vec3<f32>) -> vec3<f32> {
+    let view = -incoming;
+    let cos_v = dot(normal, view);
+    let cos_l = dot(normal, outgoing);
+    if cos_v <= 0.0 || cos_l <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    let half_vector = normalize(view + outgoing);
+    let cos_h = dot(outgoing, half_vector);
+
+    var result = vec3<f32>(0.0);
+    var remaining = 1.0;
+
+    if front_face {
+        let clearcoat_fresnel = material.clearcoat * fresnel_dielectric(cos_v, 1.0 / 1.5);
+        let clearcoat_alpha = material.clearcoat_roughness * material.clearcoat_roughness;
+        result += clearcoat_fresnel * eval_ggx(normal, view, outgoing, clearcoat_alpha);
+        remaining *= 1.0 - clearcoat_fresnel;
+
+        let sheen_probability = clamp(max(material.sheen_color.r, max(material.sheen_color.g, material.sheen_color.b)), 0.0, 1.0);
+        result += remaining * eval_sheen(material, view, outgoing, normal);
+        remaining *= 1.0 - sheen_probability;
+    }
+
+    let alpha = material.roughness * material.roughness;
+    let specular = eval_ggx(normal, view, outgoing, alpha);
+
+    let metal = fresnel_schlick(material.albedo, vec3<f32>(1.0), cos_h) * specular;
+
+    let f0_ior = pow((material.ior - 1.0) / (material.ior + 1.0), 2.0);
+    let f0 = min(f0_ior * material.specular_color, vec3<f32>(1.0)) * material.specular;
+    let f90 = vec3<f32>(material.specular);
+    let dielectric = fresnel_schlick(f0, f90, cos_h) * specular + material.albedo * (1.0 - fresnel_schlick(f0, f90, cos_v)) / PI;
+
+    // the base is mix(mix(dielectric, transmitted, transmission), metal, metallic) and the
+    // transmitted lobe isn't evaluated
+    result += remaining * mix((1.0 - material.transmission) * dielectric, metal, material.metallic);
+
+    return result * cos_l;
+}
+
+// GGX specular brdf without the fresnel term, zero for perfectly smooth surfaces
+fn eval_ggx(normal: vec3<f32>, view: vec3<f32>, light: vec3<f32>, alpha: f32) -> f32 {
+    if alpha <= 0.001 {
+        return 0.0;
+    }
+
+    let cos_v = dot(normal, view);
+    let cos_l = dot(normal, light);
+    let cos_n = dot(normal, normalize(view + light));
+
+    let alpha2 = alpha * alpha;
+    let denom = cos_n * cos_n * (alpha2 - 1.0) + 1.0;
+    let d = alpha2 / (PI * denom * denom);
+    let g = smith_g1_ggx(cos_v, alpha) * smith_g1_ggx(cos_l, alpha);
+    return d * g / (4.0 * cos_v * cos_l);
+}
+
+// uniformly samples a direction inside the cone around `axis`
+fn sample_cone(axis: vec3<f32>, cos_max_angle: f32) -> vec3<f32> {
+    let u = rand_vec2(0.0, 1.0);
+    let cos_theta = mix(1.0, cos_max_angle, u.x);
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = 2.0 * PI * u.y;
+    return to_world(vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta), axis);
+}
+
+// samples the layered material stochastically: each layer is picked with the probability
+// of light interacting with it, so the layers below only receive the remaining energy.
+// returns false if the path should be terminated
+// `specular` is set when the sampled lobe isn't covered by eval_material, lights hit by such
+// rays have to be accounted for directly
+fn sample_material(material: Material, normal: vec3<f32>, front_face: bool, direction: ptr<function, vec3<f32>>, contribution: ptr<function, vec3<f32>>, transmitted: ptr<function, bool>, specular: ptr<function, bool>) -> bool {
+    let incoming = *direction;
+    let cos_n = -dot(incoming, normal);
+    *transmitted = false;
+    *specular = false;
+
+    // the clearcoat and sheen layers only exist on the outside of the surface
+    if front_face {
+        let clearcoat_fresnel = material.clearcoat * fresnel_dielectric(cos_n, 1.0 / 1.5);
+        if rand(0.0, 1.0) < clearcoat_fresnel {
+            let alpha = material.clearcoat_roughness * material.clearcoat_roughness;
+            *specular = alpha <= 0.001;
+            let weight = sample_ggx_reflection(incoming, normal, alpha, direction);
+            *contribution *= weight;
+            return weight > 0.0;
+        }
+
+        // approximates the sheen directional albedo with its maximum, which keeps the base
+        // layer scaling of the glTF sheen model on the conservative side
+        let sheen_probability = clamp(max(material.sheen_color.r, max(material.sheen_color.g, material.sheen_color.b)), 0.0, 1.0);
+        if rand(0.0, 1.0) < sheen_probability {
+            *direction = normalize(rand_unit_sphere() + normal);
+            *contribution *= eval_sheen(material, -incoming, *direction, normal) * PI / sheen_probability;
+            return true;
+        }
+    }
+
+    let alpha = material.roughness * material.roughness;
+    *specular = alpha <= 0.001;
+
+    // the base mixes the metal over the dielectric, which mixes transmission over reflection
+    // and diffuse
+    if rand(0.0, 1.0) < material.metallic {
+        let weight = sample_ggx_reflection(incoming, normal, alpha, direction);
+        let cos_h = dot(*direction, normalize(*direction - incoming));
+        *contribution *= fresnel_schlick(material.albedo, vec3<f32>(1.0), cos_h) * weight;
+        return weight > 0.0;
+    }
+
+    if rand(0.0, 1.0) < material.transmission {
+        *specular = true;
+        return sample_dielectric(material, normal, front_face, direction, contribution, transmitted);
+    }
+
+    // dielectric base: specular reflection on top of the diffuse lobe (KHR_materials_specular)
+    let f0_ior = pow((material.ior - 1.0) / (material.ior + 1.0), 2.0);
+    let f0 = min(f0_ior * material.specular_color, vec3<f32>(1.0)) * material.specular;
+    let f90 = vec3<f32>(material.specular);
+    let specular_fresnel = fresnel_schlick(f0, f90, cos_n);
+    let specular_probability = clamp(max(specular_fresnel.r, max(specular_fresnel.g, specular_fresnel.b)), 0.0, 0.999);
+
+    if rand(0.0, 1.0) < specular_probability {
+        let weight = sample_ggx_reflection(incoming, normal, alpha, direction);
+        let cos_h = dot(*direction, normalize(*direction - incoming));
+        *contribution *= fresnel_schlick(f0, f90, cos_h) * weight / specular_probability;
+        return weight > 0.0;
+    }
+
+    *contribution *= material.albedo * (1.0 - specular_fresnel) / (1.0 - specular_probability);
+    *specular = false;
+    *direction = normalize(rand_unit_sphere() + normal);
+    return true;
+}
+
+// samples reflection or transmission through a smooth or rough (GGX) dielectric boundary,
+// returns false if the sampled direction ends up on the wrong side of the surface
+fn sample_dielectric(material: Material, normal: vec3<f32>, front_face: bool, direction: ptr<function, vec3<f32>>, contribution: ptr<function, vec3<f32>>, transmitted: ptr<function, bool>) -> bool {
+    let incoming = *direction;
+    let eta = select(material.ior, 1.0 / material.ior, front_face);
+    let alpha = material.roughness * material.roughness;
+
+    let microfacet_normal = sample_microfacet_normal(normal, alpha);
+
+    let cos_i = -dot(incoming, microfacet_normal);
+    if cos_i <= 0.0 {
+        return false;
+    }
+
+    let fresnel = fresnel_dielectric(cos_i, eta);
+
+    var outgoing: vec3<f32>;
+    if rand(0.0, 1.0) < fresnel {
+        // reflection (always taken on total internal reflection)
+        outgoing = reflect(incoming, microfacet_normal);
+        *transmitted = false;
+        if dot(outgoing, normal) <= 0.0 {
+            return false;
+        }
+    } else {
+        if material.thin_walled != 0u {
+            // thin-walled surfaces don't bend the ray, it exits on the other side
+            outgoing = reflect(incoming, microfacet_normal);
+            outgoing -= 2.0 * dot(outgoing, normal) * normal;
+        } else {
+            outgoing = refract(incoming, microfacet_normal, eta);
+        }
+        *transmitted = true;
+        if dot(outgoing, normal) >= 0.0 {
+            return false;
+        }
+        *contribution *= material.albedo;
+    }
+
+    *contribution *= microfacet_weight(incoming, outgoing, normal, microfacet_normal, alpha);
+    *direction = normalize(outgoing);
+    return true;
+}
+
+// samples a GGX reflection and returns its weight, or zero if the reflected direction ends up
+// below the surface
+fn sample_ggx_reflection(incoming: vec3<f32>, normal: vec3<f32>, alpha: f32, direction: ptr<function, vec3<f32>>) -> f32 {
+    let microfacet_normal = sample_microfacet_normal(normal, alpha);
+    let outgoing = reflect(incoming, microfacet_normal);
+    if dot(outgoing, normal) <= 0.0 || dot(incoming, microfacet_normal) >= 0.0 {
+        return 0.0;
+    }
+
+    *direction = normalize(outgoing);
+    return microfacet_weight(incoming, outgoing, normal, microfacet_normal, alpha);
+}
+
+fn sample_microfacet_normal(normal: vec3<f32>, alpha: f32) -> vec3<f32> {
+    if alpha <= 0.001 {
+        return normal;
+    }
+    return sample_ggx_normal(normal, alpha);
+}
+
+// weight of a microfacet normal sampled proportionally to D(m) * dot(m, n), without the fresnel term
+fn microfacet_weight(incoming: vec3<f32>, outgoing: vec3<f32>, normal: vec3<f32>, microfacet_normal: vec3<f32>, alpha: f32) -> f32 {
+    if alpha <= 0.001 {
+        return 1.0;
+    }
+
+    let cos_i = -dot(incoming, normal);
+    let cos_o = abs(dot(outgoing, normal));
+    let g = smith_g1_ggx(cos_i, alpha) * smith_g1_ggx(cos_o, alpha);
+    return g * -dot(incoming, microfacet_normal) / (cos_i * dot(microfacet_normal, normal));
+}
+
+// charlie sheen distribution with the visibility term from "Production Friendly Microfacet Sheen BRDF"
+fn eval_sheen(material: Material, view: vec3<f32>, light: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let n_dot_v = max(dot(normal, view), 0.0001);
+    let n_dot_l = max(dot(normal, light), 0.0001);
+    let n_dot_h = clamp(dot(normal, normalize(view + light)), 0.0, 1.0);
+
+    let alpha = max(material.sheen_roughness * material.sheen_roughness, 0.000001);
+    let inv_alpha = 1.0 / alpha;
+    let sin2_h = max(1.0 - n_dot_h * n_dot_h, 0.0078125);
+    let d = (2.0 + inv_alpha) * pow(sin2_h, inv_alpha * 0.5) / (2.0 * PI);
+    let v = 1.0 / (4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v));
+    return material.sheen_color * d * v;
+}
+
+fn fresnel_schlick(f0: vec3<f32>, f90: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (f90 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// eta is the ratio of the index of refraction on the incident side over the transmitted side
+fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = sqrt(1.0 - sin2_t);
+    let rs = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let rp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    return 0.5 * (rs * rs + rp * rp);
+}
+
+fn smith_g1_ggx(cos_theta: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    return 2.0 * cos_theta / (cos_theta + sqrt(alpha2 + (1.0 - alpha2) * cos_theta * cos_theta));
+}
+
+fn sample_ggx_normal(normal: vec3<f32>, alpha: f32) -> vec3<f32> {
+    let u = rand_vec2(0.0, 1.0);
+    let cos_theta = sqrt((1.0 - u.x) / (1.0 + (alpha * alpha - 1.0) * u.x));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = 2.0 * PI * u.y;
+    return to_world(vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta), normal);
+}
+
+// transforms a direction from a local frame where z is up to world space
+fn to_world(v: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let s = select(-1.0, 1.0, normal.z >= 0.0);
+    let a = -1.0 / (s + normal.z);
+    let b = normal.x * normal.y * a;
+    let tangent = vec3<f32>(1.0 + s * normal.x * normal.x * a, s * b, -s * normal.x);
+    let bitangent = vec3<f32>(b, s + normal.y * normal.y * a, -normal.y);
+    return v.x * tangent + v.y * bitangent + v.z * normal;
+}
+
+fn sample_texture(tex: texture_2d<f32>, uv: vec2<f32>, level: u32) -> vec4<f32> {
+    let size = textureDimensions(tex, level);
 
     let coord = clamp(uv, vec2(0.0), vec2(1.0)) * vec2<f32>(size);
 
     let pixel = vec2<u32>(floor(coord));
     let frac = fract(coord);
 
-    let s0 = textureLoad(tex, (pixel + vec2(0u, 0u)) % size, 0);
-    let s1 = textureLoad(tex, (pixel + vec2(0u, 1u)) % size, 0);
-    let s2 = textureLoad(tex, (pixel + vec2(1u, 0u)) % size, 0);
-    let s3 = textureLoad(tex, (pixel + vec2(1u, 1u)) % size, 0);
+    let s0 = textureLoad(tex, (pixel + vec2(0u, 0u)) % size, level);
+    let s1 = textureLoad(tex, (pixel + vec2(0u, 1u)) % size, level);
+    let s2 = textureLoad(tex, (pixel + vec2(1u, 0u)) % size, level);
+    let s3 = textureLoad(tex, (pixel + vec2(1u, 1u)) % size, level);
+
+    return s0 * (1.0 - frac.x) * (1.0 - frac.y) + s1 * (1.0 - frac.x) * frac.y + s2 * frac.x * (1.0 - frac.y) + s3 * frac.x * frac.y;
+}
+
+// bilinearly filtered lookup with repeat wrapping, srgb textures are decoded to linear
+fn sample_material_texture(texture_index: u32, uv: vec2<f32>) -> vec4<f32> {
+    let texture = load_texture(texture_index);
+    let size = vec2<u32>(texture.width, texture.height);
+
+    let coord = fract(uv) * vec2<f32>(size) - 0.5;
+    let base = floor(coord);
+    let frac = coord - base;
+    let pixel = vec2<u32>(vec2<i32>(base) + vec2<i32>(size)) % size;
+    let next = (pixel + 1u) % size;
+
+    let s0 = load_texel(texture, vec2(pixel.x, pixel.y));
+    let s1 = load_texel(texture, vec2(pixel.x, next.y));
+    let s2 = load_texel(texture, vec2(next.x, pixel.y));
+    let s3 = load_texel(texture, vec2(next.x, next.y));
 
     return s0 * (1.0 - frac.x) * (1.0 - frac.y) + s1 * (1.0 - frac.x) * frac.y + s2 * frac.x * (1.0 - frac.y) + s3 * frac.x * frac.y;
 }
 
+// the texture headers are stored in front of the texels
+fn load_texture(texture_index: u32) -> Texture {
+    let base = texture_index * 4u;
+    return Texture(b_texels[base], b_texels[base + 1u], b_texels[base + 2u], b_texels[base + 3u]);
+}
+
+fn load_texel(texture: Texture, pixel: vec2<u32>) -> vec4<f32> {
+    let texel = unpack4x8unorm(b_texels[texture.offset + pixel.y * texture.width + pixel.x]);
+    if texture.srgb != 0u {
+        return vec4<f32>(srgb_to_linear(texel.rgb), texel.a);
+    }
+    return texel;
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
 fn sample_environment(dir: vec3<f32>) -> vec3<f32> {
     let inv_atan = vec2(0.1591, 0.3183);
     let uv = vec2(atan2(dir.z, dir.x), asin(-dir.y)) * inv_atan + 0.5;
-    return sample_texture(t_environment, uv).rgb;
+    return sample_texture(t_environment, uv, 0u).rgb;
+}
+
+// blends the two nearest box filtered mip levels
+fn sample_environment_lod(dir: vec3<f32>, lod: f32) -> vec3<f32> {
+    let inv_atan = vec2(0.1591, 0.3183);
+    let uv = vec2(atan2(dir.z, dir.x), asin(-dir.y)) * inv_atan + 0.5;
+    let max_level = textureNumLevels(t_environment) - 1u;
+    let clamped_lod = clamp(lod, 0.0, f32(max_level));
+    let level = u32(clamped_lod);
+    let next_level = min(level + 1u, max_level);
+    return mix(sample_texture(t_environment, uv, level), sample_texture(t_environment, uv, next_level), fract(clamped_lod)).rgb;
+}
+
+// analytic fit of the split sum environment brdf (Karis, "Physically Based Shading on Mobile")
+fn env_brdf_approx(f0: vec3<f32>, roughness: f32, cos_v: f32) -> vec3<f32> {
+    let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = min(r.x * r.x, exp2(-9.28 * cos_v)) * r.x + r.y;
+    let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    return f0 * ab.x + ab.y;
 }
 
 fn aces_approx(x: vec3<f32>) -> vec3<f32> {
@@ -18,6 +18,10 @@ impl<T: bytemuck::Pod, U> Buffer<T, U> {
         self.len
     }
 
+    pub fn size_in_bytes(&self) -> u64 {
+        self.inner.size()
+    }
+
     pub fn as_entire_binding(&self) -> wgpu::BindingResource {
         self.inner.as_entire_binding()
     }
@@ -26,6 +30,16 @@ impl<T: bytemuck::Pod, U> Buffer<T, U> {
         debug_assert_eq!(self.len, data.len());
         queue.write_buffer(&self.inner, 0, bytemuck::cast_slice(data));
     }
+
+    // `offset` counts elements, not bytes
+    pub fn write_at(&self, queue: &wgpu::Queue, offset: usize, data: &[T]) {
+        debug_assert!(offset + data.len() <= self.len);
+        queue.write_buffer(
+            &self.inner,
+            (offset * mem::size_of::<T>()) as u64,
+            bytemuck::cast_slice(data),
+        );
+    }
 }
 
 impl<T: bytemuck::Pod> Buffer<T, Uniform> {
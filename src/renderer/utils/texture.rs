@@ -30,11 +30,7 @@ impl Texture2D {
         let view = inner.create_view(&wgpu::TextureViewDescriptor {
             label: Some(label),
             format: None,
-            dimension: Some(if mip_level_count == 1 {
-                wgpu::TextureViewDimension::D2
-            } else {
-                wgpu::TextureViewDimension::D2Array
-            }),
+            dimension: Some(wgpu::TextureViewDimension::D2),
             aspect: wgpu::TextureAspect::All,
             base_mip_level: 0,
             mip_level_count: Some(mip_level_count),
@@ -45,6 +41,17 @@ impl Texture2D {
         Self { inner, view }
     }
 
+    pub fn size_in_bytes(&self) -> u64 {
+        let block_size = self.inner.format().block_size(None).unwrap_or(0) as u64;
+        (0..self.inner.mip_level_count())
+            .map(|level| {
+                let width = (self.inner.width() >> level).max(1) as u64;
+                let height = (self.inner.height() >> level).max(1) as u64;
+                width * height * block_size
+            })
+            .sum()
+    }
+
     pub fn inner(&self) -> &wgpu::Texture {
         &self.inner
     }
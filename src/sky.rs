@@ -0,0 +1,237 @@
+use std::f32::consts::PI;
+
+use crate::{environment::equirect_direction, Environment, Light, LightKind, ValidationError};
+
+// analytic daylight model from "A Practical Analytic Model for Daylight" (Preetham et al.),
+// radiance is in nits and the sun illuminance in lux to match the renderer units. angles are in
+// radians, the azimuth is measured from +x towards +z and y is up
+#[derive(Clone, Debug)]
+pub struct Sky {
+    pub sun_elevation: f32,
+    pub sun_azimuth: f32,
+    pub turbidity: f32,
+    pub ground_albedo: glam::Vec3,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            sun_elevation: 45f32.to_radians(),
+            sun_azimuth: 0.0,
+            turbidity: 3.0,
+            ground_albedo: glam::Vec3::splat(0.3),
+        }
+    }
+}
+
+impl Sky {
+    // 0.53 degrees
+    const SUN_ANGULAR_DIAMETER: f32 = 0.00925;
+    const SOLAR_ILLUMINANCE: f32 = 127500.0;
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !self.sun_elevation.is_finite() || !self.sun_azimuth.is_finite() {
+            return Err(ValidationError::Sky("sun angles must be finite"));
+        }
+        if !(1.7..=10.0).contains(&self.turbidity) {
+            return Err(ValidationError::Sky("turbidity must be between 1.7 and 10"));
+        }
+        if !(self.ground_albedo.cmpge(glam::Vec3::ZERO).all()
+            && self.ground_albedo.cmple(glam::Vec3::ONE).all())
+        {
+            return Err(ValidationError::Sky(
+                "ground albedo must be between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+
+    // points towards the sun
+    pub fn sun_direction(&self) -> glam::Vec3 {
+        glam::vec3(
+            self.sun_elevation.cos() * self.sun_azimuth.cos(),
+            self.sun_elevation.sin(),
+            self.sun_elevation.cos() * self.sun_azimuth.sin(),
+        )
+    }
+
+    // directional light matching the sun of the sky, without intensity once the sun has set
+    pub fn sun_light(&self) -> Light {
+        let transmittance = if self.sun_elevation > 0.0 {
+            self.sun_transmittance()
+        } else {
+            glam::Vec3::ZERO
+        };
+        let luminance = luminance(transmittance);
+        let color = if luminance > 0.0 {
+            transmittance / luminance
+        } else {
+            glam::Vec3::ONE
+        };
+
+        Light {
+            kind: LightKind::Directional {
+                direction: -self.sun_direction(),
+                angular_diameter: Self::SUN_ANGULAR_DIAMETER,
+            },
+            color,
+            intensity: Self::SOLAR_ILLUMINANCE * luminance,
+            ..Default::default()
+        }
+    }
+
+    // sky radiance seen along `direction`, the ground below the horizon is a lambertian surface
+    // lit by the sun and `sky_irradiance`
+    pub fn radiance(&self, direction: glam::Vec3, sky_irradiance: glam::Vec3) -> glam::Vec3 {
+        if direction.y < 0.0 {
+            let sun = self.sun_light();
+            let sun_irradiance = sun.color * sun.intensity * self.sun_elevation.sin().max(0.0);
+            return self.ground_albedo * (sun_irradiance + sky_irradiance) / PI;
+        }
+
+        // the model isn't valid for the sun below the horizon, it fades out through twilight
+        let sun_zenith = (PI / 2.0 - self.sun_elevation).min(PI / 2.0);
+        let twilight =
+            ((self.sun_elevation + 6f32.to_radians()) / 6f32.to_radians()).clamp(0.0, 1.0);
+        let sun_direction = glam::vec3(
+            sun_zenith.sin() * self.sun_azimuth.cos(),
+            sun_zenith.cos(),
+            sun_zenith.sin() * self.sun_azimuth.sin(),
+        );
+
+        let t = self.turbidity;
+        let zenith = direction.y.max(0.01).acos();
+        let gamma = direction.dot(sun_direction).clamp(-1.0, 1.0).acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_zenith);
+        // kcd/m^2
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let zenith_chromaticity = |m: [[f32; 4]; 3]| {
+            let angles = glam::vec4(sun_zenith.powi(3), sun_zenith.powi(2), sun_zenith, 1.0);
+            let row = |r: [f32; 4]| glam::Vec4::from(r).dot(angles);
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith_x = zenith_chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = zenith_chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = |coefficients: [(f32, f32); 5], zenith: f32, gamma: f32| {
+            let [a, b, c, d, e] = coefficients.map(|(m, c)| m * t + c);
+            (1.0 + a * (b / zenith.cos().max(0.01)).exp())
+                * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+        };
+        let relative = |coefficients: [(f32, f32); 5]| {
+            perez(coefficients, zenith, gamma) / perez(coefficients, 0.0, sun_zenith)
+        };
+
+        let luminance = zenith_luminance
+            * 1000.0
+            * relative([
+                (0.1787, -1.4630),
+                (-0.3554, 0.4275),
+                (-0.0227, 5.3251),
+                (0.1206, -2.5771),
+                (-0.0670, 0.3703),
+            ]);
+        let x = zenith_x
+            * relative([
+                (-0.0193, -0.2592),
+                (-0.0665, 0.0008),
+                (-0.0004, 0.2125),
+                (-0.0641, -0.8989),
+                (-0.0033, 0.0452),
+            ]);
+        let y = zenith_y
+            * relative([
+                (-0.0167, -0.2608),
+                (-0.0950, 0.0092),
+                (-0.0079, 0.2102),
+                (-0.0441, -1.6537),
+                (-0.0109, 0.0529),
+            ]);
+
+        (xyy_to_linear_srgb(x, y, luminance) * twilight).max(glam::Vec3::ZERO)
+    }
+
+    // extinction of sunlight through the atmosphere by rayleigh and aerosol scattering, using
+    // the air mass approximation of Kasten and Young
+    fn sun_transmittance(&self) -> glam::Vec3 {
+        let zenith_degrees = 90.0 - self.sun_elevation.to_degrees();
+        let air_mass = 1.0
+            / (zenith_degrees.to_radians().cos()
+                + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+
+        // wavelengths of the red, green and blue primaries in micrometers
+        let wavelengths = glam::vec3(0.680, 0.550, 0.440);
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let optical_depth = |lambda: f32| 0.008735 * lambda.powf(-4.08) + beta * lambda.powf(-1.3);
+
+        glam::vec3(
+            (-air_mass * optical_depth(wavelengths.x)).exp(),
+            (-air_mass * optical_depth(wavelengths.y)).exp(),
+            (-air_mass * optical_depth(wavelengths.z)).exp(),
+        )
+    }
+}
+
+impl Environment {
+    // bakes the sky into an equirectangular environment, the sun itself is left out since it's
+    // sampled through `Sky::sun_light`
+    pub fn from_sky(sky: &Sky, size: glam::UVec2) -> Self {
+        let direction = |x, y| equirect_direction(x, y, size);
+
+        let mut pixels = vec![glam::Vec4::ZERO; (size.x * size.y) as usize];
+        let mut sky_irradiance = glam::Vec3::ZERO;
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let direction = direction(x, y);
+                if direction.y < 0.0 {
+                    continue;
+                }
+
+                let radiance = sky.radiance(direction, glam::Vec3::ZERO);
+                let solid_angle = (2.0 * PI / size.x as f32)
+                    * (PI / size.y as f32)
+                    * (1.0 - direction.y * direction.y).sqrt();
+                sky_irradiance += radiance * direction.y * solid_angle;
+                pixels[(y * size.x + x) as usize] = radiance.extend(1.0);
+            }
+        }
+
+        let ground = sky.radiance(-glam::Vec3::Y, sky_irradiance).extend(1.0);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if direction(x, y).y < 0.0 {
+                    pixels[(y * size.x + x) as usize] = ground;
+                }
+            }
+        }
+
+        Self::from_pixels(size, &pixels)
+    }
+}
+
+fn luminance(color: glam::Vec3) -> f32 {
+    color.dot(glam::vec3(0.2126, 0.7152, 0.0722))
+}
+
+fn xyy_to_linear_srgb(x: f32, y: f32, luminance: f32) -> glam::Vec3 {
+    if y <= 0.0 {
+        return glam::Vec3::ZERO;
+    }
+
+    let xyz = glam::vec3(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+    glam::vec3(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
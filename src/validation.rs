@@ -0,0 +1,138 @@
+// describes why a scene description was rejected, indices refer to the offending element
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    Settings(&'static str),
+    Camera(&'static str),
+    Sky(&'static str),
+    EmptyEnvironment,
+    EnvironmentDataSize { size: glam::UVec2, len: usize },
+    NoMaterials,
+    NoVertices,
+    NoTriangles,
+    Texture { index: usize, reason: &'static str },
+    IesProfile { index: usize, reason: &'static str },
+    TooManyIesProfiles { len: usize, max: usize },
+    Light { index: usize, reason: &'static str },
+    LightIesProfileOutOfRange { light: usize, ies_profile: u32 },
+    MaterialTextureOutOfRange { material: usize, texture: u32 },
+    MaterialTexCoordOutOfRange { material: usize, tex_coord: u32 },
+    VertexNotFinite { vertex: usize },
+    VertexOutOfRange { triangle: usize, vertex: u32 },
+    MaterialOutOfRange { triangle: usize, material: u32 },
+    MeshTrianglesOutOfRange { mesh: usize },
+    InstanceMeshOutOfRange { instance: usize, mesh: u32 },
+    InstanceMaterialOutOfRange { instance: usize, material: u32 },
+    InstanceTransform { instance: usize },
+    Primitive { index: usize, reason: &'static str },
+    PrimitiveMaterialOutOfRange { primitive: usize, material: u32 },
+    InstanceOutOfRange { instance: usize },
+    MaterialIndexOutOfRange { material: usize },
+    MaterialCount { expected: usize, len: usize },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::Settings(reason) => write!(f, "invalid renderer settings: {}", reason),
+            ValidationError::Camera(reason) => write!(f, "invalid camera: {}", reason),
+            ValidationError::Sky(reason) => write!(f, "invalid sky: {}", reason),
+            ValidationError::EmptyEnvironment => write!(f, "environment has a zero size"),
+            ValidationError::EnvironmentDataSize { size, len } => write!(
+                f,
+                "environment of {}x{} expects {} bytes of data, got {}",
+                size.x,
+                size.y,
+                size.x as usize * size.y as usize * std::mem::size_of::<glam::Vec4>(),
+                len
+            ),
+            ValidationError::NoMaterials => write!(f, "geometry has no materials"),
+            ValidationError::NoVertices => write!(f, "geometry has no vertices or primitives"),
+            ValidationError::NoTriangles => write!(f, "geometry has no triangles or primitives"),
+            ValidationError::Texture { index, reason } => {
+                write!(f, "invalid texture {}: {}", index, reason)
+            }
+            ValidationError::IesProfile { index, reason } => {
+                write!(f, "invalid ies profile {}: {}", index, reason)
+            }
+            ValidationError::TooManyIesProfiles { len, max } => {
+                write!(f, "{} ies profiles exceed the maximum of {}", len, max)
+            }
+            ValidationError::Light { index, reason } => {
+                write!(f, "invalid light {}: {}", index, reason)
+            }
+            ValidationError::LightIesProfileOutOfRange { light, ies_profile } => write!(
+                f,
+                "light {} references ies profile {} which doesn't exist",
+                light, ies_profile
+            ),
+            ValidationError::MaterialTextureOutOfRange { material, texture } => write!(
+                f,
+                "material {} references texture {} which doesn't exist",
+                material, texture
+            ),
+            ValidationError::MaterialTexCoordOutOfRange {
+                material,
+                tex_coord,
+            } => write!(
+                f,
+                "material {} uses texture coordinate set {}, vertices only have sets 0 and 1",
+                material, tex_coord
+            ),
+            ValidationError::VertexNotFinite { vertex } => {
+                write!(f, "vertex {} has an attribute that isn't finite", vertex)
+            }
+            ValidationError::VertexOutOfRange { triangle, vertex } => write!(
+                f,
+                "triangle {} references vertex {} which doesn't exist",
+                triangle, vertex
+            ),
+            ValidationError::MaterialOutOfRange { triangle, material } => write!(
+                f,
+                "triangle {} references material {} which doesn't exist",
+                triangle, material
+            ),
+            ValidationError::MeshTrianglesOutOfRange { mesh } => {
+                write!(f, "mesh {} references triangles which don't exist", mesh)
+            }
+            ValidationError::InstanceMeshOutOfRange { instance, mesh } => write!(
+                f,
+                "instance {} references mesh {} which doesn't exist",
+                instance, mesh
+            ),
+            ValidationError::InstanceMaterialOutOfRange { instance, material } => write!(
+                f,
+                "instance {} references material {} which doesn't exist",
+                instance, material
+            ),
+            ValidationError::InstanceTransform { instance } => write!(
+                f,
+                "instance {} has a transform that isn't finite or invertible",
+                instance
+            ),
+            ValidationError::Primitive { index, reason } => {
+                write!(f, "invalid primitive {}: {}", index, reason)
+            }
+            ValidationError::PrimitiveMaterialOutOfRange {
+                primitive,
+                material,
+            } => write!(
+                f,
+                "primitive {} references material {} which doesn't exist",
+                primitive, material
+            ),
+            ValidationError::InstanceOutOfRange { instance } => {
+                write!(f, "instance {} doesn't exist", instance)
+            }
+            ValidationError::MaterialIndexOutOfRange { material } => {
+                write!(f, "material {} doesn't exist", material)
+            }
+            ValidationError::MaterialCount { expected, len } => write!(
+                f,
+                "expected {} materials to replace the existing ones, got {}",
+                expected, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}